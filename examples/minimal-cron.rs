@@ -1,4 +1,4 @@
-use ancymon::{handlers::DebugBuilder, triggers::cron::CronTrigger, Bot, Config, Value};
+use ancymon::{handlers::DebugBuilder, triggers::cron::CronTrigger, Bot, Config};
 use std::fs;
 
 #[tokio::main]
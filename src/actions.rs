@@ -1,16 +1,46 @@
 use serde::Deserialize;
+use std::time::Duration;
 
-use crate::values::Value;
+use crate::{
+    config::deserialize_duration,
+    errors::{AncymonError, RuntimeErrorKind},
+    expressions::Expression,
+    templates::Template,
+};
+
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
 
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct Action {
+    /// Used in logs and error messages, defaults to `event -> emit`.
+    #[serde(default)]
+    pub(crate) name: Option<String>,
     pub(crate) handler: String,
     pub(crate) event: String,
     pub(crate) emit: String,
-    pub(crate) arguments: Value,
+    /// Rendered against the event before every execution.
+    pub(crate) arguments: Template,
     #[serde(default)]
     #[serde(rename = "accepted-input")]
     pub(crate) accepted_input: AcceptedInput,
+    /// Only execute when the expression matches the accepted input.
+    #[serde(default)]
+    pub(crate) when: Option<Expression>,
+    #[serde(default)]
+    pub(crate) retry: Option<RetryPolicy>,
+    /// Deadline for a single handler execution, see `[defaults] timeout`.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl Action {
+    pub(crate) fn name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{} -> {}", self.event, self.emit),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
@@ -21,3 +51,121 @@ pub enum AcceptedInput {
     Ok,
     Err,
 }
+
+/// Exponential backoff for failed handler executions.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct RetryPolicy {
+    /// Total number of executions, including the first one.
+    #[serde(rename = "max-attempts", default = "default_max_attempts")]
+    pub(crate) max_attempts: u32,
+    #[serde(
+        rename = "initial-delay",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    pub(crate) initial_delay: Option<Duration>,
+    #[serde(
+        rename = "max-delay",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    pub(crate) max_delay: Option<Duration>,
+    #[serde(default = "default_multiplier")]
+    pub(crate) multiplier: f64,
+    /// Randomize the delays, so failing actions do not retry in lockstep.
+    #[serde(default = "default_jitter")]
+    pub(crate) jitter: bool,
+    /// Runtime error kinds worth retrying. Other errors are emitted right away.
+    #[serde(rename = "retry-on", default = "default_retry_on")]
+    pub(crate) retry_on: Vec<RuntimeErrorKind>,
+}
+impl RetryPolicy {
+    /// Delay before the next attempt, after `attempt` failed ones.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let initial = self.initial_delay.unwrap_or(DEFAULT_INITIAL_DELAY);
+        let max = self.max_delay.unwrap_or(DEFAULT_MAX_DELAY);
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = initial.as_secs_f64() * self.multiplier.max(1.).powi(exponent);
+        let delay = Duration::try_from_secs_f64(delay).unwrap_or(max).min(max);
+        if self.jitter {
+            // Keep at least half of the delay.
+            delay.mul_f64(0.5 + rand::random::<f64>() / 2.)
+        } else {
+            delay
+        }
+    }
+    pub(crate) fn is_retryable(&self, error: &AncymonError) -> bool {
+        match error {
+            AncymonError::RuntimeError(e) => self.retry_on.contains(&e.kind()),
+            _ => false,
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_multiplier() -> f64 {
+    2.
+}
+
+fn default_jitter() -> bool {
+    true
+}
+
+fn default_retry_on() -> Vec<RuntimeErrorKind> {
+    vec![RuntimeErrorKind::Handler]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{ConfigError, RuntimeError};
+
+    fn policy(s: &str) -> RetryPolicy {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = policy(
+            r#"
+            initial-delay = "100ms"
+            max-delay = "1s"
+            jitter = false
+            "#,
+        );
+        assert_eq!(policy.max_attempts, 3);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+        assert_eq!(policy.delay(5), Duration::from_secs(1));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn backoff_jitter() {
+        let policy = policy("initial-delay = 1\nmultiplier = 3");
+        for _ in 0..100 {
+            let delay = policy.delay(2);
+            assert!(delay >= Duration::from_millis(1500));
+            assert!(delay <= Duration::from_secs(3));
+        }
+    }
+
+    #[test]
+    fn retryable() {
+        let policy = policy(r#"retry-on = ["handler", "rejected"]"#);
+        assert!(policy.is_retryable(
+            &RuntimeError::Handler {
+                message: String::new(),
+                source: None
+            }
+            .into()
+        ));
+        assert!(policy.is_retryable(&RuntimeError::Rejected(String::new()).into()));
+        assert!(!policy.is_retryable(&RuntimeError::InvalidArguments(String::new()).into()));
+        assert!(!policy.is_retryable(&ConfigError::ParsingError(String::new()).into()));
+    }
+}
@@ -1,58 +1,125 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::{
+    sync::{
+        mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender},
+        oneshot, OwnedSemaphorePermit,
+    },
+    task::{JoinError, JoinHandle, JoinSet},
+};
 
 use crate::{
     actions::{AcceptedInput, Action},
     config::Config,
-    errors::{AncymonError, ConfigError},
-    events::Event,
+    errors::{report, ActionError, AncymonError, ConfigError, RuntimeError},
+    events::{Event, EventValue},
+    expressions::Scope,
     handlers::{EventHandler, HandlerBuilder},
-    triggers::{Trigger, TriggerSource},
+    limits::{Limiter, Limits, Overflow, Overflowed},
+    triggers::{SourceCheck, Trigger, TriggerSource},
+    validation::{self, Diagnostics},
     values::Value,
 };
 
 const QUEUE_SIZE: usize = 256;
 
+type Source = Box<dyn TriggerSource + Send + Sync>;
+
 struct BotContext {
     actions: HashMap<String, Vec<Action>>,
     handlers: HashMap<String, Box<dyn EventHandler + Send + Sync>>,
+    limiter: Option<Limiter>,
+    handler_limiters: HashMap<String, Limiter>,
     tx: Sender<Event>,
 }
 
+enum Command {
+    Reload(Box<Config>, oneshot::Sender<Result<(), AncymonError>>),
+    Shutdown(Duration, oneshot::Sender<ShutdownSummary>),
+}
+
+/// Outcome of a graceful shutdown.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShutdownSummary {
+    /// Queued events executed after the shutdown was requested.
+    pub drained: usize,
+    /// Event executions that finished before the timeout.
+    pub completed: usize,
+    /// Event executions aborted at the timeout.
+    pub aborted: usize,
+    /// Queued events never executed because of the timeout.
+    pub dropped: usize,
+}
+
+struct Control {
+    tx: UnboundedSender<Command>,
+    rx: UnboundedReceiver<Command>,
+}
+impl Default for Control {
+    fn default() -> Self {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        Self { tx, rx }
+    }
+}
+
+/// Trigger source spawned on the runtime. Hands the source back once stopped.
+struct RunningSource {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<Source>,
+}
+
 #[derive(Default)]
 pub struct Bot {
-    handler_builders: HashMap<String, Box<dyn HandlerBuilder>>,
-    trigger_sources: HashMap<String, Box<dyn TriggerSource + Send + Sync>>,
+    handler_builders: HashMap<String, Box<dyn HandlerBuilder + Send + Sync>>,
+    trigger_sources: HashMap<String, Source>,
+    /// Checks of the registered source types, available while the sources are running.
+    source_checks: HashMap<String, SourceCheck>,
+    control: Control,
 }
 impl Bot {
-    pub async fn run(mut self, config: Config) -> Result<(), AncymonError> {
-        let handlers = self.build_handlers(&config).await?;
-        let actions = self.build_actions(&config).await?;
-
-        self.init_trigger_sources(&config).await?;
-        let sources = self.trigger_sources.into_values().collect();
-
+    /// Run the bot until it is shut down through a [`BotHandle`].
+    pub async fn run(mut self, config: Config) -> Result<ShutdownSummary, AncymonError> {
+        check(self.validate(&config))?;
         let (tx, rx) = tokio::sync::mpsc::channel(QUEUE_SIZE);
+        let context = self.build_context(&config, tx.clone()).await?;
 
-        let context = BotContext {
-            actions,
-            handlers,
-            tx: tx.clone(),
-        };
+        let triggers = triggers_by_source(&config);
+        let names = Vec::from_iter(triggers.keys().cloned());
+        self.init_trigger_sources(&config, triggers).await?;
 
-        spawn_sources(sources, tx).await;
-        run(context, rx).await?;
-
-        Ok(())
+        let sources = self.spawn_sources(names, tx);
+        self.event_loop(config, context, sources, rx).await
     }
-    pub fn with_handler_type<T: HandlerBuilder + 'static>(
+    /// Check the config against the registered handler and source types, including the
+    /// handler limits and the trigger arguments, and look for dangling handlers,
+    /// unconsumed events, unreachable actions and cycles.
+    pub fn validate(&self, config: &Config) -> Diagnostics {
+        validation::validate(
+            config,
+            |a| self.handler_builders.contains_key(a),
+            |a| self.source_checks.get(a).copied(),
+        )
+    }
+    /// Handle used to control the bot once it is running.
+    pub fn handle(&self) -> BotHandle {
+        BotHandle {
+            tx: self.control.tx.clone(),
+        }
+    }
+    pub fn with_handler_type<T: HandlerBuilder + Send + Sync + 'static>(
         mut self,
         name: impl Into<String>,
         builder: T,
     ) -> Self {
-        self.handler_builders
-            .insert(name.into(), Box::new(builder) as Box<dyn HandlerBuilder>);
+        self.handler_builders.insert(
+            name.into(),
+            Box::new(builder) as Box<dyn HandlerBuilder + Send + Sync>,
+        );
         self
     }
 
@@ -61,13 +128,28 @@ impl Bot {
         name: impl Into<String>,
         source: T,
     ) -> Self {
-        self.trigger_sources.insert(
-            name.into(),
-            Box::new(source) as Box<dyn TriggerSource + Send + Sync>,
-        );
+        let name = name.into();
+        self.source_checks.insert(name.clone(), T::check);
+        self.trigger_sources
+            .insert(name, Box::new(source) as Source);
         self
     }
 
+    async fn build_context(
+        &self,
+        config: &Config,
+        tx: Sender<Event>,
+    ) -> Result<BotContext, AncymonError> {
+        let handlers = self.build_handlers(config).await?;
+        Ok(BotContext {
+            actions: self.build_actions(config, &handlers).await?,
+            handlers,
+            limiter: Limiter::new(&config.limits),
+            handler_limiters: build_handler_limiters(config)?,
+            tx,
+        })
+    }
+
     async fn build_handlers(
         &self,
         config: &Config,
@@ -98,30 +180,41 @@ impl Bot {
     async fn build_actions(
         &self,
         config: &Config,
+        handlers: &HashMap<String, Box<dyn EventHandler + Send + Sync>>,
     ) -> Result<HashMap<String, Vec<Action>>, AncymonError> {
         let mut actions: HashMap<String, Vec<Action>> = HashMap::new();
 
         for action in config.actions.iter() {
+            let literal = handlers
+                .get(&action.handler)
+                .map(|a| a.literal_arguments())
+                .unwrap_or_default();
+            if let Some(key) = literal.iter().find(|a| action.arguments.renders_key(a)) {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Action `{}`: templates are not allowed in `{key}` of handler `{}`, \
+                    bind the event values instead",
+                    action.name(),
+                    action.handler
+                ))
+                .into());
+            }
+            let mut action = action.clone();
+            action.timeout = action.timeout.or(config.defaults.timeout);
+
             if let Some(event) = actions.get_mut(&action.event) {
-                event.push(action.clone());
+                event.push(action);
                 continue;
             }
-            actions.insert(action.event.to_string(), vec![action.clone()]);
+            actions.insert(action.event.to_string(), vec![action]);
         }
         Ok(actions)
     }
 
-    async fn init_trigger_sources(&mut self, config: &Config) -> Result<(), AncymonError> {
-        let mut triggers: HashMap<String, Vec<Trigger>> = HashMap::new();
-
-        for trigger in config.triggers.iter() {
-            if let Some(entry) = triggers.get_mut(&trigger.source) {
-                entry.push(trigger.clone());
-                continue;
-            }
-            triggers.insert(trigger.source.to_string(), vec![trigger.clone()]);
-        }
-
+    async fn init_trigger_sources(
+        &mut self,
+        config: &Config,
+        triggers: HashMap<String, Vec<Trigger>>,
+    ) -> Result<(), AncymonError> {
         for (source_name, triggers) in triggers {
             let source = self
                 .trigger_sources
@@ -141,58 +234,1478 @@ impl Bot {
 
         Ok(())
     }
+
+    /// Spawn the given initialized sources.
+    fn spawn_sources(
+        &mut self,
+        names: impl IntoIterator<Item = String>,
+        tx: Sender<Event>,
+    ) -> HashMap<String, RunningSource> {
+        let mut running = HashMap::new();
+
+        for source_name in names {
+            let Some(mut source) = self.trigger_sources.remove(&source_name) else {
+                continue;
+            };
+            let source_tx = tx.clone();
+            let (stop, stopped) = oneshot::channel();
+            let handle = tokio::spawn(async move {
+                tokio::select! {
+                    _ = source.run(source_tx) => {},
+                    _ = stopped => {},
+                }
+                source
+            });
+            running.insert(source_name, RunningSource { stop, handle });
+        }
+        running
+    }
+
+    /// Stop the running sources and take them back, so they can be initialized again.
+    async fn stop_sources(&mut self, running: HashMap<String, RunningSource>) {
+        for (source_name, source) in running {
+            let _ = source.stop.send(());
+            match source.handle.await {
+                Ok(source) => {
+                    self.trigger_sources.insert(source_name, source);
+                }
+                Err(e) => tracing::error!("Source {source_name} could not be stopped: {e}"),
+            }
+        }
+    }
+
+    /// Initialize the given sources with the previous config again, after a failed reload.
+    /// Returns the ones restored, sources failing again are logged and left stopped.
+    async fn restore_sources(&mut self, current: &Config, names: &HashSet<String>) -> Vec<String> {
+        let mut restored = Vec::new();
+        for (source_name, triggers) in triggers_by_source(current) {
+            if !names.contains(&source_name) {
+                continue;
+            }
+            let triggers = HashMap::from([(source_name.to_string(), triggers)]);
+            match self.init_trigger_sources(current, triggers).await {
+                Ok(()) => restored.push(source_name),
+                Err(e) => tracing::error!("Source {source_name} could not be restored: {e}"),
+            }
+        }
+        restored
+    }
+
+    async fn event_loop(
+        mut self,
+        mut config: Config,
+        context: BotContext,
+        mut sources: HashMap<String, RunningSource>,
+        mut rx: Receiver<Event>,
+    ) -> Result<ShutdownSummary, AncymonError> {
+        tracing::info!("Ancymon Bot is starting...");
+        let tx = context.tx.clone();
+        let mut context = Arc::new(context);
+        let mut tasks = JoinSet::new();
+
+        let (timeout, reply) = loop {
+            tokio::select! {
+                event = next_event(&mut rx, &context) => {
+                    let Some((event, permit)) = event else {
+                        return Err(RuntimeError::Bot("Event queue closed".to_string()).into());
+                    };
+                    tracing::info!("Executing event: {}", event.name);
+                    tasks.spawn(execute_event(event, permit, Arc::clone(&context)));
+                }
+                Some(result) = tasks.join_next() => log_task_result(result),
+                Some(command) = self.control.rx.recv() => match command {
+                    Command::Reload(new_config, reply) => {
+                        let result = self
+                            .reload(&config, &new_config, &mut context, &mut sources, &tx)
+                            .await;
+                        if result.is_ok() {
+                            config = *new_config;
+                        }
+                        let _ = reply.send(result);
+                    }
+                    Command::Shutdown(timeout, reply) => break (timeout, reply),
+                }
+            }
+        };
+
+        tracing::info!("Ancymon Bot is shutting down...");
+        self.stop_sources(sources).await;
+        let summary = drain(&context, &mut rx, &mut tasks, timeout).await;
+        tracing::info!("Ancymon Bot stopped: {summary:?}");
+        let _ = reply.send(summary.clone());
+        Ok(summary)
+    }
+
+    /// Rebuild handlers and actions from the new config, and restart the sources
+    /// whose config or triggers changed. Other sources keep running undisturbed.
+    /// On failure the previous setup keeps running, except the sources that
+    /// could not be restored.
+    async fn reload(
+        &mut self,
+        current: &Config,
+        config: &Config,
+        context: &mut Arc<BotContext>,
+        sources: &mut HashMap<String, RunningSource>,
+        tx: &Sender<Event>,
+    ) -> Result<(), AncymonError> {
+        tracing::info!("Reloading config...");
+        check(self.validate(config))?;
+        let new_context = self.build_context(config, tx.clone()).await?;
+
+        let changed = changed_sources(current, config);
+        let stopped = changed
+            .iter()
+            .filter_map(|a| Some((a.to_string(), sources.remove(a)?)))
+            .collect();
+        self.stop_sources(stopped).await;
+
+        let mut triggers = triggers_by_source(config);
+        triggers.retain(|name, _| changed.contains(name));
+        let names = Vec::from_iter(triggers.keys().cloned());
+        if let Err(e) = self.init_trigger_sources(config, triggers).await {
+            tracing::error!("Config reload failed, restoring previous sources");
+            let restored = self.restore_sources(current, &changed).await;
+            sources.extend(self.spawn_sources(restored, tx.clone()));
+            return Err(e);
+        }
+
+        // Events already being executed keep their reference to the old context.
+        *context = Arc::new(new_context);
+        sources.extend(self.spawn_sources(names, tx.clone()));
+        tracing::info!("Config reloaded");
+        Ok(())
+    }
+}
+
+/// Cloneable handle to a running [`Bot`].
+#[derive(Clone)]
+pub struct BotHandle {
+    tx: UnboundedSender<Command>,
+}
+impl BotHandle {
+    /// Swap in a new config. Events already in flight finish with the previous one.
+    pub async fn reload(&self, config: Config) -> Result<(), AncymonError> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Command::Reload(Box::new(config), tx))
+            .map_err(|_| RuntimeError::Bot("Bot is not running".to_string()))?;
+        rx.await
+            .map_err(|_| RuntimeError::Bot("Bot is not running".to_string()))?
+    }
+    /// Stop the trigger sources, then keep executing the queued events until the queue
+    /// is empty or the timeout passes. Executions still running at the timeout are aborted.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<ShutdownSummary, AncymonError> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Command::Shutdown(timeout, tx))
+            .map_err(|_| RuntimeError::Bot("Bot is not running".to_string()))?;
+        rx.await
+            .map_err(|_| RuntimeError::Bot("Bot is not running".to_string()).into())
+    }
+    /// Poll the config file, the files it includes and its secret files for changes and
+    /// reload the bot when one of them is modified, or when the includes match other files.
+    /// Runs until the bot stops.
+    pub async fn watch(self, path: impl Into<PathBuf>, interval: Duration) {
+        let path = path.into();
+        let modified = |files: &[PathBuf]| {
+            Vec::from_iter(
+                files
+                    .iter()
+                    .map(|a| std::fs::metadata(a).and_then(|a| a.modified()).ok()),
+            )
+        };
+        let mut files = match Config::from_file(&path) {
+            Ok(config) => config.files,
+            Err(_) => vec![path.clone()],
+        };
+        let mut last = modified(&files);
+
+        loop {
+            tokio::time::sleep(interval).await;
+            if self.tx.is_closed() {
+                return;
+            }
+            // Composed again on every poll, so new files matching the includes are noticed.
+            let config = Config::from_file(&path);
+            let current_files = match &config {
+                Ok(config) => config.files.clone(),
+                Err(_) => files.clone(),
+            };
+            let current = modified(&current_files);
+            if current_files == files && current == last {
+                continue;
+            }
+            files = current_files;
+            last = current;
+
+            let result = match config {
+                Ok(config) => self.reload(config).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                tracing::error!("Config reload failed: {e}");
+            }
+        }
+    }
 }
 
-async fn run(context: BotContext, mut rx: Receiver<Event>) -> Result<(), AncymonError> {
-    tracing::info!("Ancymon Bot is starting...");
-    let context = Arc::new(context);
+/// Execute the remaining events, including the ones emitted meanwhile by the running actions.
+async fn drain(
+    context: &Arc<BotContext>,
+    rx: &mut Receiver<Event>,
+    tasks: &mut JoinSet<()>,
+    timeout: Duration,
+) -> ShutdownSummary {
+    let mut summary = ShutdownSummary::default();
+
+    let drained = tokio::time::timeout(timeout, async {
+        loop {
+            if tasks.is_empty() {
+                // The context keeps a sender, so the queue is never closed here.
+                let Ok(event) = rx.try_recv() else { break };
+                summary.drained += 1;
+                tasks.spawn(execute_event(event, None, Arc::clone(context)));
+                continue;
+            }
+            tokio::select! {
+                Some(event) = rx.recv() => {
+                    summary.drained += 1;
+                    tasks.spawn(execute_event(event, None, Arc::clone(context)));
+                }
+                Some(result) = tasks.join_next() => {
+                    summary.completed += 1;
+                    log_task_result(result);
+                }
+            }
+        }
+    })
+    .await;
+
+    if drained.is_err() {
+        summary.aborted = tasks.len();
+        tasks.abort_all();
+        while rx.try_recv().is_ok() {
+            summary.dropped += 1;
+        }
+        tracing::warn!(
+            "Shutdown timed out: {} executions aborted, {} events dropped",
+            summary.aborted,
+            summary.dropped
+        );
+    }
+    summary
+}
 
-    while let Some(event) = rx.recv().await {
-        tracing::info!("Executing event: {}", event.name);
-        // TODO add concurrent events limit? (tokio::Semaphore?)
-        let event_context = Arc::clone(&context);
-        tokio::spawn(execute_event(event, event_context));
+fn log_task_result(result: Result<(), JoinError>) {
+    if let Err(e) = result
+        && e.is_panic()
+    {
+        tracing::error!("Event execution panicked: {e}");
     }
+}
 
+/// Log the warnings of the validation and fail on its errors.
+fn check(diagnostics: Diagnostics) -> Result<(), AncymonError> {
+    for warning in diagnostics.warnings.iter() {
+        tracing::warn!("Config: {warning}");
+    }
+    if !diagnostics.is_ok() {
+        return Err(ConfigError::Validation(diagnostics.errors.join("; ")).into());
+    }
     Ok(())
 }
 
-async fn spawn_sources(sources: Vec<Box<dyn TriggerSource + Send + Sync>>, tx: Sender<Event>) {
-    for mut source in sources {
-        // TODO take join handle ?
-        let source_tx = tx.clone();
-        tokio::spawn(async move { source.run(source_tx).await });
+/// Sources whose config or triggers differ between the two configs.
+fn changed_sources(current: &Config, config: &Config) -> HashSet<String> {
+    let current_triggers = triggers_by_source(current);
+    let triggers = triggers_by_source(config);
+    current_triggers
+        .keys()
+        .chain(triggers.keys())
+        .filter(|a| {
+            current_triggers.get(*a) != triggers.get(*a)
+                || current.sources.get(*a) != config.sources.get(*a)
+        })
+        .cloned()
+        .collect()
+}
+
+fn triggers_by_source(config: &Config) -> HashMap<String, Vec<Trigger>> {
+    let mut triggers: HashMap<String, Vec<Trigger>> = HashMap::new();
+
+    for trigger in config.triggers.iter() {
+        if let Some(entry) = triggers.get_mut(&trigger.source) {
+            entry.push(trigger.clone());
+            continue;
+        }
+        triggers.insert(trigger.source.to_string(), vec![trigger.clone()]);
     }
+    triggers
 }
 
-async fn execute_event(event: Event, context: Arc<BotContext>) {
-    for action in context.actions.get(&event.name).cloned().iter().flatten() {
+fn build_handler_limiters(config: &Config) -> Result<HashMap<String, Limiter>, AncymonError> {
+    let mut limiters = HashMap::new();
+
+    for (name, handler_config) in config.handlers.iter() {
+        let limits: Limits = handler_config.clone().try_into().map_err(|e| {
+            ConfigError::InvalidValue(format!("Invalid limits at handler config {name}: {e}"))
+        })?;
+        if let Some(limiter) = Limiter::new(&limits) {
+            limiters.insert(name.to_string(), limiter);
+        }
+    }
+    Ok(limiters)
+}
+
+/// Value passed to the action's handler, if the action accepts the event.
+fn action_input(value: &EventValue, accepted_input: AcceptedInput) -> Option<Value> {
+    match (value, accepted_input) {
+        (Ok(Value::Null), AcceptedInput::Null) => Some(Value::Null),
+        (Ok(v), AcceptedInput::NotNull) if v != &Value::Null => Some(v.clone()),
+        (Ok(v), AcceptedInput::Ok) => Some(v.clone()),
+        (Err(e), AcceptedInput::Err) => Some(Value::String(report(e))),
+        _ => None,
+    }
+}
+
+/// Receive the next event. With the `queue` overflow policy the global execution slot is
+/// taken first, so while every slot is busy the events wait in the bounded queue
+/// and the trigger sources are slowed down, instead of piling up as waiting tasks.
+async fn next_event(
+    rx: &mut Receiver<Event>,
+    context: &BotContext,
+) -> Option<(Event, Option<OwnedSemaphorePermit>)> {
+    let permit = match &context.limiter {
+        Some(limiter) if limiter.overflow() == Overflow::Queue => limiter.acquire().await.ok(),
+        _ => None,
+    };
+    Some((rx.recv().await?, permit))
+}
+
+/// `permit` is the global execution slot, when already taken by [`next_event`].
+/// The slot is released before the results are emitted: emitting waits for room in
+/// the event queue, which is only drained by the event loop once a slot is free.
+async fn execute_event(
+    event: Event,
+    permit: Option<OwnedSemaphorePermit>,
+    context: Arc<BotContext>,
+) {
+    if let Some(reply) = &event.reply {
+        reply.offer(&event);
+    }
+    let actions = context
+        .actions
+        .get(&event.name)
+        .cloned()
+        .unwrap_or_default();
+
+    let permit = match (permit, &context.limiter) {
+        (Some(permit), _) => Some(permit),
+        (None, Some(limiter)) => match limiter.acquire().await {
+            Ok(permit) => Some(permit),
+            Err(Overflowed::Dropped) => {
+                tracing::warn!("Event dropped by the concurrency limit: {}", event.name);
+                return;
+            }
+            Err(Overflowed::Rejected) => {
+                tracing::warn!("Event rejected by the concurrency limit: {}", event.name);
+                for action in actions.iter() {
+                    if action_input(&event.value, action.accepted_input).is_none() {
+                        continue;
+                    }
+                    let error = RuntimeError::Rejected(format!(
+                        "Event {} rejected by the concurrency limit",
+                        event.name
+                    ));
+                    emit(&context, &event, action, Err(error.into())).await;
+                }
+                return;
+            }
+        },
+        (None, None) => None,
+    };
+
+    let mut results = Vec::new();
+    for action in actions.iter() {
+        let Some(input) = action_input(&event.value, action.accepted_input) else {
+            continue;
+        };
+        let scope = Scope {
+            value: &input,
+            event: &event.name,
+        };
+        if let Some(when) = &action.when
+            && !when.matches(&scope)
+        {
+            continue;
+        }
+        let arguments = action.arguments.render(&scope);
         let Some(handler) = context.handlers.get(&action.handler) else {
             tracing::error!("Handler not found: {}", action.handler);
             continue;
         };
 
-        let result = match (&event.value, action.accepted_input) {
-            (Ok(Value::Null), AcceptedInput::Null) => {
-                Some(handler.execute(&Value::Null, &action.arguments).await)
+        let limiter = context.handler_limiters.get(&action.handler);
+        let Some(result) =
+            execute_action(handler.as_ref(), limiter, action, &input, &arguments).await
+        else {
+            continue;
+        };
+        let result = result.map_err(|e| {
+            ActionError {
+                action: action.name(),
+                handler: action.handler.clone(),
+                error: Box::new(e),
             }
-            (Ok(v), AcceptedInput::NotNull) if v != &Value::Null => {
-                Some(handler.execute(v, &action.arguments).await)
+            .into()
+        });
+        results.push((action, result));
+    }
+
+    drop(permit);
+    for (action, result) in results {
+        emit(&context, &event, action, result).await;
+    }
+}
+
+/// Execute the handler within its concurrency limit and the action's timeout,
+/// retrying according to the action's retry policy, rejections included.
+/// Returns `None` when the execution was dropped by the concurrency limit.
+async fn execute_action(
+    handler: &(dyn EventHandler + Send + Sync),
+    limiter: Option<&Limiter>,
+    action: &Action,
+    input: &Value,
+    arguments: &Value,
+) -> Option<EventValue> {
+    let mut attempt = 1;
+    loop {
+        let permit = match limiter {
+            Some(limiter) => limiter.acquire().await.map(Some),
+            None => Ok(None),
+        };
+        let result = match permit {
+            Err(Overflowed::Dropped) => {
+                tracing::warn!(
+                    "Action {} dropped by the concurrency limit of handler {}",
+                    action.name(),
+                    action.handler
+                );
+                return None;
             }
-            (Ok(v), AcceptedInput::Ok) => Some(handler.execute(v, &action.arguments).await),
-            (Err(e), AcceptedInput::Err) => Some(
-                handler
-                    .execute(&Value::String(format!("{e}")), &action.arguments)
-                    .await,
-            ),
-            _ => None,
+            Err(Overflowed::Rejected) => Err(RuntimeError::Rejected(format!(
+                "Action rejected by the concurrency limit of handler {}",
+                action.handler
+            ))
+            .into()),
+            // The slot is held for the execution only, not during the retry delay.
+            Ok(_permit) => match action.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, handler.execute(input, arguments))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(RuntimeError::Timeout(format!(
+                            "Action {} timed out after {timeout:?}",
+                            action.name()
+                        ))
+                        .into())
+                    }),
+                None => handler.execute(input, arguments).await,
+            },
         };
-        if let Some(result) = result {
-            context
-                .tx
-                .send(Event::new(action.emit.to_string(), result))
-                .await
-                .unwrap();
+        let (Err(e), Some(retry)) = (&result, &action.retry) else {
+            return Some(result);
+        };
+        if attempt >= retry.max_attempts || !retry.is_retryable(e) {
+            return Some(result);
         }
+        let delay = retry.delay(attempt);
+        tracing::warn!(
+            "Action {} failed (attempt {attempt}), retrying in {delay:?}: {}",
+            action.name(),
+            report(e)
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+async fn emit(context: &BotContext, event: &Event, action: &Action, value: EventValue) {
+    let child = event.child(action.emit.to_string(), value);
+    if context.tx.send(child).await.is_err() {
+        tracing::error!(
+            "Event queue closed, event {} of action {} is lost",
+            action.emit,
+            action.name()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{errors::BuildError, triggers::webhook::WebhookTrigger};
+
+    type Records = Arc<Mutex<Vec<Value>>>;
+
+    /// Emits every trigger once after starting, with the trigger arguments as value.
+    #[derive(Default)]
+    struct OnceSource {
+        triggers: Vec<Trigger>,
+    }
+    #[async_trait]
+    impl TriggerSource for OnceSource {
+        async fn init(
+            &mut self,
+            config: &toml::Table,
+            triggers: Vec<Trigger>,
+        ) -> Result<(), AncymonError> {
+            if config.contains_key("fail") {
+                return Err(BuildError::Source {
+                    message: "failing source".to_string(),
+                    source: None,
+                }
+                .into());
+            }
+            self.triggers = triggers;
+            Ok(())
+        }
+        async fn run(&mut self, tx: Sender<Event>) {
+            for trigger in self.triggers.iter() {
+                // Empty arguments emit null.
+                let value = match &trigger.arguments {
+                    toml::Value::Array(a) if a.is_empty() => Value::Null,
+                    a => a.clone().try_into().unwrap(),
+                };
+                tx.send(Event::new(trigger.emit.to_string(), Ok(value)))
+                    .await
+                    .unwrap();
+            }
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Emits the number of events given in the trigger arguments, counting the sent ones.
+    #[derive(Default)]
+    struct FloodSource {
+        triggers: Vec<Trigger>,
+        sent: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    #[async_trait]
+    impl TriggerSource for FloodSource {
+        async fn init(
+            &mut self,
+            _config: &toml::Table,
+            triggers: Vec<Trigger>,
+        ) -> Result<(), AncymonError> {
+            self.triggers = triggers;
+            Ok(())
+        }
+        async fn run(&mut self, tx: Sender<Event>) {
+            for trigger in self.triggers.iter() {
+                for _ in 0..trigger.arguments.as_integer().unwrap() {
+                    tx.send(Event::new(trigger.emit.to_string(), Ok(Value::Null)))
+                        .await
+                        .unwrap();
+                    self.sent.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Records the action arguments.
+    struct RecordHandler(Records);
+    #[async_trait]
+    impl EventHandler for RecordHandler {
+        async fn execute(&self, event: &Value, arguments: &Value) -> EventValue {
+            self.0.lock().unwrap().push(arguments.clone());
+            Ok(event.clone())
+        }
+    }
+    struct RecordBuilder(Records);
+    impl HandlerBuilder for RecordBuilder {
+        fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError> {
+            Ok(Box::new(RecordHandler(self.0.clone())))
+        }
+    }
+
+    /// Sleeps for the number of seconds given in the arguments.
+    struct SleepHandler(Records);
+    #[async_trait]
+    impl EventHandler for SleepHandler {
+        async fn execute(&self, event: &Value, arguments: &Value) -> EventValue {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Value::String("asleep".to_string()));
+            tokio::time::sleep(Duration::from_secs_f64(arguments.as_float().unwrap())).await;
+            self.0
+                .lock()
+                .unwrap()
+                .push(Value::String("awake".to_string()));
+            Ok(event.clone())
+        }
+    }
+    struct SleepBuilder(Records);
+    impl HandlerBuilder for SleepBuilder {
+        fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError> {
+            Ok(Box::new(SleepHandler(self.0.clone())))
+        }
+    }
+
+    /// Fails with a handler error the given number of times, then succeeds.
+    struct FlakyHandler(Records, Mutex<i64>);
+    #[async_trait]
+    impl EventHandler for FlakyHandler {
+        async fn execute(&self, _event: &Value, arguments: &Value) -> EventValue {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Value::String("attempt".to_string()));
+            let mut failures = self.1.lock().unwrap();
+            if *failures < arguments.as_int().unwrap() {
+                *failures += 1;
+                return Err(RuntimeError::Handler {
+                    message: "flaky".to_string(),
+                    source: None,
+                }
+                .into());
+            }
+            Ok(Value::Integer(*failures))
+        }
+    }
+    struct FlakyBuilder(Records);
+    impl HandlerBuilder for FlakyBuilder {
+        fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError> {
+            Ok(Box::new(FlakyHandler(self.0.clone(), Mutex::new(0))))
+        }
+    }
+
+    fn sleep_config(seconds: f64) -> Config {
+        Config::new(&format!(
+            r#"
+            [sources.once]
+            [handlers.sleep]
+            type = "sleep"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "sleep"
+            event = "start"
+            emit = "slept"
+            accepted-input = "Null"
+            arguments = {seconds:?}
+
+            [[actions]]
+            handler = "record"
+            event = "slept"
+            emit = "done"
+            accepted-input = "Null"
+            arguments = "recorded"
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn config(emit: &str, argument: &str) -> Config {
+        Config::new(&format!(
+            r#"
+            [sources.once]
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "{emit}"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "{emit}"
+            emit = "done"
+            accepted-input = "Null"
+            arguments = "{argument}"
+            "#
+        ))
+        .unwrap()
+    }
+
+    async fn wait_for(records: &Records, value: &str) {
+        let value = Value::String(value.to_string());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !records.lock().unwrap().contains(&value) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    fn bot(records: &Records) -> Bot {
+        Bot::default()
+            .with_handler_type("record", RecordBuilder(records.clone()))
+            .with_handler_type("sleep", SleepBuilder(records.clone()))
+            .with_handler_type("flaky", FlakyBuilder(records.clone()))
+            .with_source_type("once", OnceSource::default())
+    }
+
+    #[tokio::test]
+    async fn reload() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        tokio::spawn(bot.run(config("start", "first")));
+        wait_for(&records, "first").await;
+
+        // The changed trigger restarts the source.
+        handle.reload(config("restart", "second")).await.unwrap();
+        wait_for(&records, "second").await;
+    }
+
+    #[tokio::test]
+    async fn reload_unchanged_source() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        tokio::spawn(bot.run(config("start", "first")));
+        wait_for(&records, "first").await;
+
+        // Only the actions changed, so the source keeps running without emitting again.
+        handle.reload(config("start", "second")).await.unwrap();
+        handle.reload(config("restart", "third")).await.unwrap();
+        wait_for(&records, "third").await;
+        assert!(!records
+            .lock()
+            .unwrap()
+            .contains(&Value::String("second".to_string())));
+    }
+
+    #[tokio::test]
+    async fn reload_restore() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        tokio::spawn(bot.run(config("start", "first")));
+        wait_for(&records, "first").await;
+
+        let mut failing = config("restart", "second");
+        failing
+            .sources
+            .get_mut("once")
+            .unwrap()
+            .insert("fail".to_string(), toml::Value::Boolean(true));
+        assert!(handle.reload(failing).await.is_err());
+
+        // The restored source runs again with the previous config.
+        let first = Value::String("first".to_string());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while records
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|a| **a == first)
+                .count()
+                < 2
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let dir = crate::config::tests::TestDir::new("watch");
+        let path = dir.join("main.toml");
+        std::fs::write(
+            &path,
+            r#"
+            include = ["handlers/*.toml"]
+
+            [sources.once]
+            marker-file = "marker"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "start"
+            emit = "done"
+            accepted-input = "Null"
+            arguments = "first"
+            "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("marker"), "a").unwrap();
+
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        tokio::spawn(bot.run(Config::from_file(&path).unwrap()));
+        tokio::spawn(
+            handle
+                .clone()
+                .watch(path.clone(), Duration::from_millis(20)),
+        );
+        wait_for(&records, "first").await;
+
+        // A new file matching the includes is loaded.
+        std::fs::write(
+            dir.join("handlers/extra.toml"),
+            r#"
+            [[triggers]]
+            source = "once"
+            emit = "extra"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "extra"
+            emit = "done"
+            accepted-input = "Null"
+            arguments = "second"
+            "#,
+        )
+        .unwrap();
+        wait_for(&records, "second").await;
+
+        // A changed secret restarts the source, which emits again.
+        std::fs::write(dir.join("marker"), "b").unwrap();
+        let first = Value::String("first".to_string());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while records
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|a| **a == first)
+                .count()
+                < 3
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_invalid() {
+        let records = Records::default();
+        let mut config = config("start", "first");
+        config.actions[0].handler = "missing".to_string();
+        config.triggers[0].source = "missing".to_string();
+        let Err(e) = bot(&records).run(config).await else {
+            panic!("expected an error");
+        };
+        assert_eq!(
+            e.to_string(),
+            "config validation failed: action `start -> done`: handler `missing` is not defined; \
+            unknown source type `missing`"
+        );
+    }
+
+    #[tokio::test]
+    async fn reload_invalid() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        tokio::spawn(bot.run(config("start", "first")));
+        wait_for(&records, "first").await;
+
+        let mut invalid = config("start", "second");
+        invalid.triggers[0].source = "missing".to_string();
+        assert!(handle.reload(invalid).await.is_err());
+
+        // The previous config keeps working.
+        handle.reload(config("restart", "third")).await.unwrap();
+        wait_for(&records, "third").await;
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        let running = tokio::spawn(bot.run(sleep_config(0.1)));
+        wait_for(&records, "asleep").await;
+
+        let summary = handle.shutdown(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(summary.aborted, 0);
+        assert_eq!(summary.dropped, 0);
+        // The chained `slept` and `done` events are executed as well.
+        assert_eq!(summary.drained, 2);
+        assert_eq!(summary.completed, 3);
+        assert!(records
+            .lock()
+            .unwrap()
+            .contains(&Value::String("recorded".to_string())));
+        assert_eq!(running.await.unwrap().unwrap(), summary);
+    }
+
+    #[tokio::test]
+    async fn shutdown_timeout() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let handle = bot.handle();
+        tokio::spawn(bot.run(sleep_config(60.)));
+        wait_for(&records, "asleep").await;
+
+        let summary = handle.shutdown(Duration::from_millis(50)).await.unwrap();
+        assert_eq!(summary.aborted, 1);
+        assert_eq!(summary.completed, 0);
+        assert!(handle.reload(sleep_config(0.)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn handler_limit_reject() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.sleep]
+            type = "sleep"
+            max-concurrent = 1
+            overflow = "reject"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "sleep"
+            event = "start"
+            emit = "slept"
+            accepted-input = "Null"
+            arguments = 60.0
+
+            [[actions]]
+            handler = "record"
+            event = "slept"
+            emit = "done"
+            accepted-input = "Err"
+            arguments = "rejected"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot.run(config));
+        wait_for(&records, "rejected").await;
+        let asleep = records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|a| a.as_str() == Some("asleep"))
+            .count();
+        assert_eq!(asleep, 1);
+    }
+
+    #[tokio::test]
+    async fn handler_limit_retry() {
+        let records = Records::default();
+        let bot = bot(&records);
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.sleep]
+            type = "sleep"
+            max-concurrent = 1
+            overflow = "reject"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "sleep"
+            event = "start"
+            emit = "slept"
+            accepted-input = "Null"
+            arguments = 0.05
+            retry = { max-attempts = 20, initial-delay = "20ms", multiplier = 1, retry-on = ["rejected"] }
+
+            [[actions]]
+            handler = "record"
+            event = "slept"
+            emit = "done"
+            accepted-input = "Err"
+            arguments = "rejected"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot.run(config));
+
+        // The rejected execution is retried until the first one frees the slot.
+        let awake = Value::String("awake".to_string());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while records
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|a| **a == awake)
+                .count()
+                < 2
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(!records
+            .lock()
+            .unwrap()
+            .contains(&Value::String("rejected".to_string())));
+    }
+
+    #[tokio::test]
+    async fn global_limit_backpressure() {
+        let records = Records::default();
+        let source = FloodSource::default();
+        let sent = source.sent.clone();
+        let bot = bot(&records).with_source_type("flood", source);
+        let config = Config::new(
+            r#"
+            [sources.flood]
+            [handlers.sleep]
+            type = "sleep"
+
+            [limits]
+            max-concurrent = 1
+
+            [[triggers]]
+            source = "flood"
+            emit = "start"
+            arguments = 1000
+
+            [[actions]]
+            handler = "sleep"
+            event = "start"
+            emit = "slept"
+            accepted-input = "Null"
+            arguments = 60.0
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot.run(config));
+        wait_for(&records, "asleep").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // One event executing, the rest waiting in the queue until the source is blocked.
+        let sent = sent.load(std::sync::atomic::Ordering::Relaxed);
+        assert!(sent <= QUEUE_SIZE + 2, "{sent}");
+    }
+
+    #[tokio::test]
+    async fn global_limit_emit_full_queue() {
+        let records = Records::default();
+        let bot = bot(&records).with_source_type("flood", FloodSource::default());
+        let config = Config::new(
+            r#"
+            [sources.flood]
+            [handlers.record]
+            type = "record"
+
+            [limits]
+            max-concurrent = 1
+
+            [[triggers]]
+            source = "flood"
+            emit = "start"
+            arguments = 1000
+
+            [[actions]]
+            handler = "record"
+            event = "start"
+            emit = "done"
+            accepted-input = "Null"
+            arguments = "recorded"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot.run(config));
+
+        // The actions emit while the source keeps the queue full.
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while records.lock().unwrap().len() < 1000 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    fn retry_config(failures: i64, max_attempts: u32) -> Config {
+        Config::new(&format!(
+            r#"
+            [sources.once]
+            [handlers.flaky]
+            type = "flaky"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "flaky"
+            event = "start"
+            emit = "done"
+            accepted-input = "Null"
+            arguments = {failures}
+            retry = {{ max-attempts = {max_attempts}, initial-delay = "1ms" }}
+
+            [[actions]]
+            handler = "record"
+            event = "done"
+            emit = "recorded"
+            arguments = "succeeded"
+
+            [[actions]]
+            handler = "record"
+            event = "done"
+            emit = "recorded"
+            accepted-input = "Err"
+            arguments = "failed"
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn attempts(records: &Records) -> usize {
+        records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|a| a.as_str() == Some("attempt"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        let records = Records::default();
+        tokio::spawn(bot(&records).run(retry_config(2, 3)));
+        wait_for(&records, "succeeded").await;
+        assert_eq!(attempts(&records), 3);
+        assert!(!records
+            .lock()
+            .unwrap()
+            .contains(&Value::String("failed".to_string())));
+    }
+
+    #[tokio::test]
+    async fn retry_exhausted() {
+        let records = Records::default();
+        tokio::spawn(bot(&records).run(retry_config(5, 2)));
+        wait_for(&records, "failed").await;
+        assert_eq!(attempts(&records), 2);
+    }
+
+    #[tokio::test]
+    async fn action_timeout() {
+        let records = Records::default();
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.sleep]
+            type = "sleep"
+            [handlers.record]
+            type = "record"
+
+            [defaults]
+            timeout = "10ms"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "sleep"
+            event = "start"
+            emit = "slept"
+            accepted-input = "Null"
+            arguments = 60.0
+
+            [[actions]]
+            handler = "sleep"
+            event = "start"
+            emit = "slept"
+            accepted-input = "Null"
+            arguments = 0.1
+            timeout = 5
+
+            [[actions]]
+            handler = "record"
+            event = "slept"
+            emit = "done"
+            accepted-input = "Err"
+            arguments = "timed out"
+
+            [[actions]]
+            handler = "record"
+            event = "slept"
+            emit = "done"
+            accepted-input = "Ok"
+            arguments = "finished"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot(&records).run(config));
+        wait_for(&records, "timed out").await;
+        wait_for(&records, "finished").await;
+    }
+
+    #[tokio::test]
+    async fn error_message() {
+        let records = Records::default();
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.flaky]
+            type = "flaky"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            name = "unreliable"
+            handler = "flaky"
+            event = "start"
+            emit = "attempted"
+            accepted-input = "Null"
+            arguments = 1
+
+            [[actions]]
+            handler = "record"
+            event = "attempted"
+            emit = "done"
+            accepted-input = "Err"
+            arguments = "{{ value }}"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot(&records).run(config));
+        wait_for(
+            &records,
+            "action `unreliable` (handler `flaky`): handler error: flaky",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn action_when() {
+        let records = Records::default();
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "reading"
+            arguments = { temp = 35, sensor = "attic" }
+
+            [[actions]]
+            handler = "record"
+            event = "reading"
+            emit = "done"
+            when = "value.temp > 30 && value.sensor == 'garage'"
+            arguments = "garage"
+
+            [[actions]]
+            handler = "record"
+            event = "reading"
+            emit = "done"
+            when = "value.temp > 30 && value.sensor == 'attic'"
+            arguments = "attic"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot(&records).run(config));
+        wait_for(&records, "attic").await;
+        assert!(!records
+            .lock()
+            .unwrap()
+            .contains(&Value::String("garage".to_string())));
+    }
+
+    #[test]
+    fn invalid_when() {
+        let config = Config::new(
+            r#"
+            sources = {}
+            handlers = {}
+            triggers = []
+
+            [[actions]]
+            handler = "record"
+            event = "start"
+            emit = "done"
+            when = "value.temp >"
+            arguments = []
+            "#,
+        );
+        assert!(config.is_err());
+    }
+
+    #[tokio::test]
+    async fn action_template() {
+        let records = Records::default();
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "reading"
+            arguments = { temp = 35 }
+
+            [[actions]]
+            handler = "record"
+            event = "reading"
+            emit = "done"
+            arguments = "{{ value.temp }}°C at {{ event.name }}"
+            "#,
+        )
+        .unwrap();
+        tokio::spawn(bot(&records).run(config));
+        wait_for(&records, "35°C at reading").await;
+    }
+
+    #[tokio::test]
+    async fn literal_arguments() {
+        let records = Records::default();
+        let bot = bot(&records).with_handler_type("sql", crate::handlers::sql::SqlBuilder);
+        let config = Config::new(
+            r#"
+            [sources.once]
+            [handlers.database]
+            type = "sql"
+            connection-string = "sqlite::memory:"
+
+            [[triggers]]
+            source = "once"
+            emit = "message"
+            arguments = "hello"
+
+            [[actions]]
+            name = "store"
+            handler = "database"
+            event = "message"
+            emit = "stored"
+            arguments = { query = "INSERT INTO messages VALUES ('{{ value }}')", execute = true }
+            "#,
+        )
+        .unwrap();
+        let Err(e) = bot.run(config).await else {
+            panic!("expected an error");
+        };
+        assert!(
+            e.to_string()
+                .contains("templates are not allowed in `query` of handler `database`"),
+            "{e}"
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_response() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let records = Records::default();
+        let config = Config::new(&format!(
+            r#"
+            [sources.webhook]
+            bind = "127.0.0.1:{port}"
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "webhook"
+            emit = "request"
+            arguments = {{ path = "/hook", response-event = "response" }}
+
+            [[actions]]
+            handler = "record"
+            event = "request"
+            emit = "recorded"
+            arguments = "request"
+
+            [[actions]]
+            handler = "record"
+            event = "recorded"
+            emit = "response"
+            arguments = "recorded"
+            "#
+        ))
+        .unwrap();
+        tokio::spawn(
+            bot(&records)
+                .with_source_type("webhook", WebhookTrigger::default())
+                .run(config),
+        );
+
+        let client = reqwest::Client::new();
+        let response = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match client
+                    .post(format!("http://127.0.0.1:{port}/hook"))
+                    .json(&serde_json::json!({ "a": 1 }))
+                    .send()
+                    .await
+                {
+                    Ok(response) => break response,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 200);
+        let body = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(body["body"]["a"], 1);
+        assert_eq!(body["path"], "/hook");
     }
 }
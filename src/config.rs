@@ -1,22 +1,647 @@
-use serde::Deserialize;
-use std::collections::HashMap;
-use toml::Table;
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use toml::{Table, Value};
 
 use crate::{
     actions::Action,
     errors::{AncymonError, ConfigError},
+    limits::Limits,
     triggers::Trigger,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     pub(crate) sources: HashMap<String, Table>,
     pub(crate) handlers: HashMap<String, Table>,
     pub(crate) actions: Vec<Action>,
     pub(crate) triggers: Vec<Trigger>,
+    /// Global limit of concurrently executed events.
+    #[serde(default)]
+    pub(crate) limits: Limits,
+    #[serde(default)]
+    pub(crate) defaults: Defaults,
+    /// Files the config was loaded from, watched for changes.
+    #[serde(skip)]
+    pub(crate) files: Vec<PathBuf>,
 }
 impl Config {
+    /// Parse a single TOML document, without includes, substitutions or secret files.
     pub fn new(s: &str) -> Result<Self, AncymonError> {
-        toml::from_str(s).map_err(|_| ConfigError::ParsingError.into())
+        toml::from_str(s).map_err(|e| ConfigError::ParsingError(parsing_error(Some(s), e)).into())
+    }
+    /// Load a config file, composed of:
+    /// - the files matched by `include = ["handlers/*.toml"]`, relative to the including file.
+    ///   Tables are merged and arrays concatenated, the including file overrides the included ones.
+    ///   A file included several times is merged once, at its first include,
+    /// - `${VAR}` and `${VAR:-default}` environment variables in every string value, including
+    ///   action arguments, templates and `when` expressions. `$${` is a literal `${`,
+    /// - secrets read from files for the `sources` and `handlers` keys suffixed with `-file`,
+    ///   eg. `token-file = "/run/secrets/discord"` sets `token`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AncymonError> {
+        Self::compose(path.as_ref(), &|a| std::env::var(a).ok())
+    }
+    /// [`Config::from_file`] with the given environment variables lookup.
+    fn compose(path: &Path, env: &dyn Fn(&str) -> Option<String>) -> Result<Self, AncymonError> {
+        let mut composition = Composition {
+            stack: Vec::new(),
+            loaded: HashSet::new(),
+            files: Vec::new(),
+            env,
+        };
+        let table = composition.load(path)?;
+        let mut config = Self::deserialize(table).map_err(|e| {
+            ConfigError::ParsingError(format!("{}: {}", path.display(), parsing_error(None, e)))
+        })?;
+        config.files = composition.files;
+        Ok(config)
+    }
+}
+
+/// Files of a config being composed.
+struct Composition<'a> {
+    /// Canonical paths of the files including the one being loaded.
+    stack: Vec<PathBuf>,
+    /// Canonical paths of the files loaded so far.
+    loaded: HashSet<PathBuf>,
+    /// Config and secret files read, watched for changes.
+    files: Vec<PathBuf>,
+    env: &'a dyn Fn(&str) -> Option<String>,
+}
+impl Composition<'_> {
+    /// Read and compose a config file, empty when it was loaded already.
+    fn load(&mut self, path: &Path) -> Result<Table, AncymonError> {
+        let canonical = path.canonicalize().unwrap_or(path.to_path_buf());
+        if self.stack.contains(&canonical) {
+            return Err(ConfigError::InvalidValue(format!(
+                "Config file `{}` includes itself",
+                path.display()
+            ))
+            .into());
+        }
+        if !self.loaded.insert(canonical.clone()) {
+            return Ok(Table::new());
+        }
+        let s = std::fs::read_to_string(path).map_err(|e| {
+            ConfigError::MissingConfig(format!(
+                "Config file `{}` could not be read: {e}",
+                path.display()
+            ))
+        })?;
+        let mut table = toml::from_str::<Table>(&s).map_err(|e| {
+            ConfigError::ParsingError(format!(
+                "{}: {}",
+                path.display(),
+                parsing_error(Some(&s), e)
+            ))
+        })?;
+        self.files.push(path.to_path_buf());
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let patterns = match table.remove("include") {
+            None => Vec::new(),
+            Some(Value::Array(a)) => a
+                .into_iter()
+                .map(|a| match a {
+                    Value::String(s) => Ok(s),
+                    _ => Err(ConfigError::InvalidValueType(format!(
+                        "Expected strings for key `include` in `{}`",
+                        path.display()
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(_) => {
+                return Err(ConfigError::InvalidValueType(format!(
+                    "Expected array for key `include` in `{}`",
+                    path.display()
+                ))
+                .into());
+            }
+        };
+
+        substitute_table(&mut table, self.env)?;
+        read_secrets(&mut table, &dir, &mut self.files)?;
+
+        self.stack.push(canonical);
+        let mut composed = Table::new();
+        for pattern in patterns {
+            for included in glob(&dir.join(substitute(&pattern, self.env)?)) {
+                merge(&mut composed, self.load(&included)?);
+            }
+        }
+        self.stack.pop();
+        merge(&mut composed, table);
+        Ok(composed)
+    }
+}
+
+/// Merge tables recursively and concatenate arrays.
+/// Other values of `overlay` replace the ones of `base`.
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            (Some(Value::Array(base)), Value::Array(overlay)) => base.extend(overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn substitute_table(
+    table: &mut Table,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), AncymonError> {
+    for (_, value) in table.iter_mut() {
+        substitute_value(value, env)?;
+    }
+    Ok(())
+}
+
+fn substitute_value(
+    value: &mut Value,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), AncymonError> {
+    match value {
+        Value::String(s) => *s = substitute(s, env)?,
+        Value::Array(a) => {
+            for value in a.iter_mut() {
+                substitute_value(value, env)?;
+            }
+        }
+        Value::Table(t) => substitute_table(t, env)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replace `${VAR}` and `${VAR:-default}` with environment variables.
+fn substitute(s: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<String, AncymonError> {
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(ConfigError::InvalidValue(format!(
+                "Unclosed environment variable in `{s}`"
+            )))?;
+        let variable = &rest[start + 2..start + end];
+        let (name, default) = match variable.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (variable, None),
+        };
+        match (env(name), default) {
+            (Some(value), _) => result.push_str(&value),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) => {
+                return Err(ConfigError::MissingValue(format!(
+                    "Environment variable `{name}` is not set"
+                ))
+                .into());
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Replace the `X-file` keys of every source and handler config with `X`,
+/// set to the trimmed content of the file. The secret files are added to `files`.
+fn read_secrets(
+    table: &mut Table,
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), AncymonError> {
+    for section in ["sources", "handlers"] {
+        let Some(Value::Table(section)) = table.get_mut(section) else {
+            continue;
+        };
+        for (name, config) in section.iter_mut() {
+            let Value::Table(config) = config else {
+                continue;
+            };
+            let keys = Vec::from_iter(config.keys().filter(|a| a.ends_with("-file")).cloned());
+            for key in keys {
+                let secret_key = key.trim_end_matches("-file").to_string();
+                if config.contains_key(&secret_key) {
+                    return Err(ConfigError::InvalidValue(format!(
+                        "Both `{secret_key}` and `{key}` set in `{name}`"
+                    ))
+                    .into());
+                }
+                let Some(Value::String(file)) = config.remove(&key) else {
+                    return Err(ConfigError::InvalidValueType(format!(
+                        "Expected string for key `{key}` in `{name}`"
+                    ))
+                    .into());
+                };
+                let path = dir.join(&file);
+                let secret = std::fs::read_to_string(&path).map_err(|e| {
+                    ConfigError::MissingValue(format!(
+                        "Secret file `{file}` for key `{key}` in `{name}` could not be read: {e}"
+                    ))
+                })?;
+                files.push(path);
+                config.insert(secret_key, Value::String(secret.trim().to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Paths matching a pattern with `*` and `?` wildcards in its components, sorted.
+/// Components without wildcards are kept even when the file does not exist.
+fn glob(pattern: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let component = component.as_os_str();
+        let Some(wildcard) = component.to_str().filter(|a| a.contains(['*', '?'])) else {
+            paths.iter_mut().for_each(|a| a.push(component));
+            continue;
+        };
+        paths = paths
+            .iter()
+            .flat_map(|dir| {
+                let entries = match dir.as_os_str().is_empty() {
+                    true => std::fs::read_dir("."),
+                    false => std::fs::read_dir(dir),
+                };
+                let mut matches = Vec::from_iter(
+                    entries
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .map(|a| a.file_name())
+                        .filter(|a| a.to_str().is_some_and(|a| wildcard_match(a, wildcard)))
+                        .map(|a| dir.join(a)),
+                );
+                matches.sort();
+                matches
+            })
+            .collect();
+    }
+    paths
+}
+
+fn wildcard_match(name: &str, pattern: &str) -> bool {
+    let name = Vec::from_iter(name.chars());
+    let pattern = Vec::from_iter(pattern.chars());
+    let (mut n, mut p) = (0, 0);
+    // Position after the last `*` and the name position it currently matches up to.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|a| *a == '*')
+}
+
+/// Message with the position and key path of a TOML error,
+/// eg. ``line 3, column 11: invalid type: string "a", expected u32 in `limits.max-concurrent` ``.
+/// The position is left out without the source, eg. for composed configs.
+pub(crate) fn parsing_error(s: Option<&str>, mut error: toml::de::Error) -> String {
+    let position = s.zip(error.span()).map(|(s, span)| {
+        let before = &s[..span.start.min(s.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        format!("line {line}, column {column}: ")
+    });
+    // Without the input, the error displays the message followed by the key path.
+    error.set_input(None);
+    let message = error.to_string().trim_end().replace('\n', " ");
+    format!("{}{message}", position.unwrap_or_default())
+}
+
+/// Settings applied to every action that does not override them.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct Defaults {
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub(crate) timeout: Option<Duration>,
+}
+
+/// Parse a duration given either as a number of seconds or as a string
+/// with a unit suffix, eg. `"500ms"`, `"90s"`, `"5m"`, `"1h"`, `"2d"`.
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let multiplier = match unit.trim() {
+        "ms" => 0.001,
+        "" | "s" => 1.,
+        "m" => 60.,
+        "h" => 3600.,
+        "d" => 86400.,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(number * multiplier).ok()
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(f64),
+    Text(String),
+}
+impl DurationValue {
+    fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Seconds(s) => Duration::try_from_secs_f64(*s).ok(),
+            Self::Text(s) => parse_duration(s),
+        }
+    }
+}
+
+/// Serde helper for optional duration fields, see [`parse_duration`].
+pub(crate) fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    let Some(value) = Option::<DurationValue>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    value
+        .duration()
+        .map(Some)
+        .ok_or(serde::de::Error::custom("invalid duration"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Deref;
+
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("1w"), None);
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("s"), None);
+    }
+
+    #[test]
+    fn parsing_errors() {
+        let base = "sources = {}\nhandlers = {}\nactions = []\ntriggers = []\n";
+
+        let Err(e) = Config::new(&format!("{base}[limits\n")) else {
+            panic!("expected an error");
+        };
+        assert!(
+            e.to_string()
+                .starts_with("invalid config: line 5, column 8: "),
+            "{e}"
+        );
+
+        let Err(e) = Config::new(&format!("{base}[limits]\nmax-concurrent = \"ten\"\n")) else {
+            panic!("expected an error");
+        };
+        let message = e.to_string();
+        assert!(
+            message.starts_with("invalid config: line 6, column 18: "),
+            "{message}"
+        );
+        assert!(
+            message.contains("invalid type: string \"ten\""),
+            "{message}"
+        );
+        assert!(message.contains("in `limits.max-concurrent`"), "{message}");
+    }
+
+    /// Empty directory for the files of a test, removed when dropped, even if the test fails.
+    pub(crate) struct TestDir(PathBuf);
+    impl TestDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("ancymon-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join("handlers")).unwrap();
+            Self(dir)
+        }
+    }
+    impl Deref for TestDir {
+        type Target = Path;
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn compose() {
+        let dir = TestDir::new("compose");
+        std::fs::write(
+            dir.join("main.toml"),
+            r#"
+            include = ["base.toml", "handlers/*.toml"]
+
+            [sources.discord]
+            token-file = "secrets/discord"
+
+            [handlers.database]
+            connection-string = "sqlite://${ANCYMON_TEST_UNSET:-/tmp}/${ANCYMON_TEST_NAME}.db"
+
+            [[actions]]
+            handler = "a"
+            event = "message"
+            emit = "replied"
+            arguments = { content = "${ANCYMON_TEST_NAME}: {{ value }}, $${not substituted}" }
+
+            [[triggers]]
+            source = "discord"
+            emit = "message"
+            arguments = { pattern = "$${not substituted}" }
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("base.toml"),
+            r#"
+            [handlers.database]
+            type = "sql"
+            connection-string = "sqlite::memory:"
+
+            [[triggers]]
+            source = "discord"
+            emit = "ready"
+            arguments = []
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("handlers/a.toml"),
+            "[handlers.a]\ntype = \"record\"",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("handlers/b.toml"),
+            "[handlers.b]\ntype = \"record\"",
+        )
+        .unwrap();
+        std::fs::write(dir.join("handlers/notes.txt"), "not toml").unwrap();
+        std::fs::create_dir(dir.join("secrets")).unwrap();
+        std::fs::write(dir.join("secrets/discord"), "secret-token\n").unwrap();
+
+        let env = |a: &str| (a == "ANCYMON_TEST_NAME").then(|| "ancymon".to_string());
+        let config = Config::compose(&dir.join("main.toml"), &env).unwrap();
+        assert_eq!(
+            config.sources["discord"].get("token"),
+            Some(&Value::String("secret-token".to_string()))
+        );
+        assert!(config.sources["discord"].get("token-file").is_none());
+        let database = &config.handlers["database"];
+        assert_eq!(
+            database.get("type"),
+            Some(&Value::String("sql".to_string()))
+        );
+        assert_eq!(
+            database.get("connection-string"),
+            Some(&Value::String("sqlite:///tmp/ancymon.db".to_string()))
+        );
+        assert!(config.handlers.contains_key("a"));
+        assert!(config.handlers.contains_key("b"));
+        assert_eq!(
+            Vec::from_iter(config.triggers.iter().map(|a| a.emit.as_str())),
+            vec!["ready", "message"]
+        );
+        assert_eq!(
+            config.triggers[1].arguments.get("pattern"),
+            Some(&Value::String("${not substituted}".to_string()))
+        );
+        // Action arguments are substituted as well, templates are left to the actions.
+        let scope = crate::expressions::Scope {
+            value: &crate::values::Value::String("hi".to_string()),
+            event: "message",
+        };
+        let crate::values::Value::Map(arguments) = config.actions[0].arguments.render(&scope)
+        else {
+            panic!("expected a map");
+        };
+        assert_eq!(
+            arguments["content"],
+            crate::values::Value::String("ancymon: hi, ${not substituted}".to_string())
+        );
+        assert_eq!(
+            config.files,
+            vec![
+                dir.join("main.toml"),
+                dir.join("secrets/discord"),
+                dir.join("base.toml"),
+                dir.join("handlers/a.toml"),
+                dir.join("handlers/b.toml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn diamond_include() {
+        let dir = TestDir::new("diamond-include");
+        let trigger = |emit: &str| {
+            format!("[[triggers]]\nsource = \"once\"\nemit = \"{emit}\"\narguments = []\n")
+        };
+        std::fs::write(
+            dir.join("main.toml"),
+            "include = [\"handlers/*.toml\"]\nsources = {}\nhandlers = {}\nactions = []\n",
+        )
+        .unwrap();
+        for name in ["a", "b"] {
+            std::fs::write(
+                dir.join(format!("handlers/{name}.toml")),
+                format!("include = [\"../common.toml\"]\n{}", trigger(name)),
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.join("common.toml"), trigger("common")).unwrap();
+
+        // The file included by both `a` and `b` is merged once.
+        let config = Config::from_file(dir.join("main.toml")).unwrap();
+        assert_eq!(
+            Vec::from_iter(config.triggers.iter().map(|a| a.emit.as_str())),
+            vec!["common", "a", "b"]
+        );
+        assert_eq!(
+            config.files,
+            vec![
+                dir.join("main.toml"),
+                dir.join("handlers/a.toml"),
+                dir.join("handlers/../common.toml"),
+                dir.join("handlers/b.toml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn compose_errors() {
+        let dir = TestDir::new("compose-errors");
+        let base = "actions = []\ntriggers = []\n";
+        let error = |s: &str| {
+            std::fs::write(dir.join("main.toml"), format!("{base}{s}")).unwrap();
+            Config::from_file(dir.join("main.toml"))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("[defaults]\ntimeout = \"${ANCYMON_TEST_UNSET}\""),
+            "missing value: Environment variable `ANCYMON_TEST_UNSET` is not set"
+        );
+        assert_eq!(
+            error("[defaults]\ntimeout = \"${ANCYMON_TEST_UNSET\""),
+            "invalid value: Unclosed environment variable in `${ANCYMON_TEST_UNSET`"
+        );
+        assert!(error("[handlers.a]\npassword-file = \"missing\"")
+            .starts_with("missing value: Secret file `missing` for key `password-file` in `a`"));
+        assert!(error("include = [\"main.toml\"]").ends_with("main.toml` includes itself"));
+        assert!(error("include = [\"missing.toml\"]").starts_with("missing config: "));
+        assert!(error("[limits]\nmax-concurrent = \"ten\"")
+            .contains("main.toml: invalid type: string \"ten\""));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("a.toml", "*.toml"));
+        assert!(wildcard_match(".toml", "*.toml"));
+        assert!(wildcard_match("a.toml.toml", "*.toml"));
+        assert!(wildcard_match("ab.toml", "a?.toml"));
+        assert!(wildcard_match("abc", "a*b*c*"));
+        assert!(!wildcard_match("a.toml.bak", "*.toml"));
+        assert!(!wildcard_match("abc.toml", "a?.toml"));
     }
 }
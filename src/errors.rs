@@ -1,34 +1,93 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+/// Underlying error of a handler or source failure, eg. a database or HTTP error.
+/// Shared, so errors stay cloneable.
+pub type ErrorSource = Arc<dyn std::error::Error + Send + Sync>;
+
+/// The error followed by its underlying causes,
+/// eg. `handler error: Sql execute failed: no such table: readings`.
+pub(crate) fn report(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut report = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        // Wrapping errors may already include the message of the wrapped one.
+        let message = error.to_string();
+        if !report.ends_with(&message) {
+            report = format!("{report}: {message}");
+        }
+        source = error.source();
+    }
+    report
+}
+
 #[derive(Clone, Debug)]
 pub enum AncymonError {
     BuildError(BuildError),
     ConfigError(ConfigError),
     RuntimeError(RuntimeError),
     ConversionError(String),
+    /// Error of a handler execution, with the action that caused it.
+    ActionError(ActionError),
 }
 
 impl std::fmt::Display for AncymonError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "TODO")
+        match self {
+            Self::BuildError(e) => write!(f, "{e}"),
+            Self::ConfigError(e) => write!(f, "{e}"),
+            Self::RuntimeError(e) => write!(f, "{e}"),
+            Self::ConversionError(message) => write!(f, "conversion error: {message}"),
+            Self::ActionError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Transparent, the source is the one of the wrapped error.
+impl std::error::Error for AncymonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::BuildError(e) => e.source(),
+            Self::ConfigError(e) => e.source(),
+            Self::RuntimeError(e) => e.source(),
+            Self::ConversionError(_) => None,
+            Self::ActionError(e) => e.source(),
+        }
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum ConfigError {
-    ParsingError,
+    /// Invalid TOML or a config that does not match the expected structure.
+    ParsingError(String),
     MissingValue(String),
     InvalidValue(String),
     InvalidValueType(String),
     MissingConfig(String),
     InvalidSource(String),
     InvalidHandlerType(String),
+    /// Errors found by [`crate::Bot::validate`].
+    Validation(String),
 }
 
 impl std::fmt::Display for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "TODO")
+        match self {
+            Self::ParsingError(message) => write!(f, "invalid config: {message}"),
+            Self::MissingValue(message) => write!(f, "missing value: {message}"),
+            Self::InvalidValue(message) => write!(f, "invalid value: {message}"),
+            Self::InvalidValueType(message) => write!(f, "invalid value type: {message}"),
+            Self::MissingConfig(message) => write!(f, "missing config: {message}"),
+            Self::InvalidSource(message) => write!(f, "invalid source: {message}"),
+            Self::InvalidHandlerType(message) => write!(f, "unknown handler type: {message}"),
+            Self::Validation(message) => write!(f, "config validation failed: {message}"),
+        }
     }
 }
 
+impl std::error::Error for ConfigError {}
+
 impl From<ConfigError> for AncymonError {
     fn from(value: ConfigError) -> Self {
         Self::ConfigError(value)
@@ -37,13 +96,32 @@ impl From<ConfigError> for AncymonError {
 
 #[derive(Clone, Debug)]
 pub enum BuildError {
-    Handler(String),
-    Source(String),
+    Handler {
+        message: String,
+        source: Option<ErrorSource>,
+    },
+    Source {
+        message: String,
+        source: Option<ErrorSource>,
+    },
 }
 
 impl std::fmt::Display for BuildError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "TODO")
+        match self {
+            Self::Handler { message, .. } => write!(f, "handler build failed: {message}"),
+            Self::Source { message, .. } => write!(f, "source build failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Handler { source, .. } | Self::Source { source, .. } => {
+                source.as_deref().map(|a| a as _)
+            }
+        }
     }
 }
 
@@ -58,13 +136,64 @@ pub enum RuntimeError {
     InvalidArguments(String),
     InvalidArgumentType(String),
     Bot(String),
-    Handler(String),
+    Handler {
+        message: String,
+        source: Option<ErrorSource>,
+    },
     Source(String),
+    /// Execution refused because of a concurrency limit.
+    Rejected(String),
+    /// Handler execution exceeded the action timeout.
+    Timeout(String),
+}
+
+impl RuntimeError {
+    pub fn kind(&self) -> RuntimeErrorKind {
+        match self {
+            Self::InvalidArguments(_) => RuntimeErrorKind::InvalidArguments,
+            Self::InvalidArgumentType(_) => RuntimeErrorKind::InvalidArgumentType,
+            Self::Bot(_) => RuntimeErrorKind::Bot,
+            Self::Handler { .. } => RuntimeErrorKind::Handler,
+            Self::Source(_) => RuntimeErrorKind::Source,
+            Self::Rejected(_) => RuntimeErrorKind::Rejected,
+            Self::Timeout(_) => RuntimeErrorKind::Timeout,
+        }
+    }
+}
+
+/// [`RuntimeError`] variant, without the message. Used to select retryable errors.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuntimeErrorKind {
+    InvalidArguments,
+    InvalidArgumentType,
+    Bot,
+    Handler,
+    Source,
+    Rejected,
+    Timeout,
 }
 
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "TODO")
+        match self {
+            Self::InvalidArguments(message) => write!(f, "invalid arguments: {message}"),
+            Self::InvalidArgumentType(message) => write!(f, "invalid argument type: {message}"),
+            Self::Bot(message) => write!(f, "bot error: {message}"),
+            Self::Handler { message, .. } => write!(f, "handler error: {message}"),
+            Self::Source(message) => write!(f, "source error: {message}"),
+            Self::Rejected(message) => write!(f, "rejected: {message}"),
+            Self::Timeout(message) => write!(f, "timed out: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Handler { source, .. } => source.as_deref().map(|a| a as _),
+            _ => None,
+        }
     }
 }
 
@@ -73,3 +202,92 @@ impl From<RuntimeError> for AncymonError {
         Self::RuntimeError(value)
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct ActionError {
+    pub action: String,
+    pub handler: String,
+    pub error: Box<AncymonError>,
+}
+
+impl std::fmt::Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "action `{}` (handler `{}`): {}",
+            self.action, self.handler, self.error
+        )
+    }
+}
+
+impl std::error::Error for ActionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl From<ActionError> for AncymonError {
+    fn from(value: ActionError) -> Self {
+        Self::ActionError(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn display() {
+        let error: AncymonError = ConfigError::MissingValue("Discord token".to_string()).into();
+        assert_eq!(error.to_string(), "missing value: Discord token");
+
+        let error: AncymonError = BuildError::Handler {
+            message: "Unknown handler `x`".to_string(),
+            source: None,
+        }
+        .into();
+        assert_eq!(
+            error.to_string(),
+            "handler build failed: Unknown handler `x`"
+        );
+
+        let error = AncymonError::ConversionError("Expected a string".to_string());
+        assert_eq!(error.to_string(), "conversion error: Expected a string");
+    }
+
+    #[test]
+    fn action_source() {
+        let cause = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "403 Forbidden");
+        let error: AncymonError = ActionError {
+            action: "notify".to_string(),
+            handler: "discord".to_string(),
+            error: Box::new(
+                RuntimeError::Handler {
+                    message: "Missing permissions".to_string(),
+                    source: Some(Arc::new(cause)),
+                }
+                .into(),
+            ),
+        }
+        .into();
+        assert_eq!(
+            error.to_string(),
+            "action `notify` (handler `discord`): handler error: Missing permissions"
+        );
+
+        let source = error.source().unwrap();
+        assert_eq!(source.to_string(), "handler error: Missing permissions");
+        // The handler keeps the underlying error.
+        let cause = source.source().unwrap();
+        assert_eq!(cause.to_string(), "403 Forbidden");
+        assert!(cause.downcast_ref::<std::io::Error>().is_some());
+        assert!(cause.source().is_none());
+
+        assert_eq!(
+            report(&error),
+            "action `notify` (handler `discord`): handler error: Missing permissions: 403 Forbidden"
+        );
+    }
+}
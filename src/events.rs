@@ -1,14 +1,101 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
 use crate::{errors::AncymonError, values::Value};
 
-pub(crate) type EventValue = Result<Value, AncymonError>;
+pub type EventValue = Result<Value, AncymonError>;
 
 #[derive(Clone, Debug)]
-pub(crate) struct Event {
+pub struct Event {
     pub(crate) name: String,
     pub(crate) value: EventValue,
+    /// Waiting for the pipeline result, inherited by the emitted events.
+    pub(crate) reply: Option<Reply>,
 }
 impl Event {
-    pub(crate) fn new(name: String, value: EventValue) -> Self {
-        Self { name, value }
+    /// Event sent by a [`TriggerSource`](crate::triggers::TriggerSource), public so
+    /// sources implemented outside the crate can create it.
+    pub fn new(name: String, value: EventValue) -> Self {
+        Self {
+            name,
+            value,
+            reply: None,
+        }
+    }
+    pub(crate) fn with_reply(mut self, reply: Reply) -> Self {
+        self.reply = Some(reply);
+        self
+    }
+    /// Event emitted while handling this one.
+    pub(crate) fn child(&self, name: String, value: EventValue) -> Self {
+        Self {
+            name,
+            value,
+            reply: self.reply.clone(),
+        }
+    }
+}
+
+/// Sends the value of the first event with the awaited name back to the event source,
+/// eg. to answer a webhook request. The receiver is closed once all the events
+/// carrying the reply are handled without reaching the awaited one.
+#[derive(Clone)]
+pub(crate) struct Reply(Arc<ReplyChannel>);
+struct ReplyChannel {
+    event: String,
+    tx: Mutex<Option<oneshot::Sender<EventValue>>>,
+}
+impl Reply {
+    pub(crate) fn new(event: String) -> (Self, oneshot::Receiver<EventValue>) {
+        let (tx, rx) = oneshot::channel();
+        let reply = Self(Arc::new(ReplyChannel {
+            event,
+            tx: Mutex::new(Some(tx)),
+        }));
+        (reply, rx)
+    }
+    pub(crate) fn offer(&self, event: &Event) {
+        if event.name != self.0.event {
+            return;
+        }
+        if let Some(tx) = self.0.tx.lock().unwrap().take() {
+            let _ = tx.send(event.value.clone());
+        }
+    }
+}
+impl std::fmt::Debug for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Reply").field(&self.0.event).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply() {
+        let (reply, mut rx) = Reply::new("done".to_string());
+        let event = Event::new("start".to_string(), Ok(Value::Null)).with_reply(reply.clone());
+        reply.offer(&event);
+        assert!(rx.try_recv().is_err());
+
+        let done = event.child("done".to_string(), Ok(Value::Integer(1)));
+        let again = event.child("done".to_string(), Ok(Value::Integer(2)));
+        reply.offer(&done);
+        reply.offer(&again);
+        assert_eq!(rx.try_recv().unwrap().unwrap(), Value::Integer(1));
+    }
+
+    #[test]
+    fn reply_dropped() {
+        let (reply, mut rx) = Reply::new("done".to_string());
+        let event = Event::new("start".to_string(), Ok(Value::Null)).with_reply(reply);
+        drop(event.child("other".to_string(), Ok(Value::Null)));
+        drop(event);
+        assert!(matches!(
+            rx.try_recv(),
+            Err(oneshot::error::TryRecvError::Closed)
+        ));
     }
 }
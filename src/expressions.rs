@@ -0,0 +1,531 @@
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+use crate::{
+    errors::{AncymonError, ConfigError},
+    values::Value,
+};
+
+/// Expression evaluated against an event, eg. `value.temp > 30 && value.sensor == "attic"`.
+///
+/// Supported syntax:
+/// - paths: `value`, `value.a.b`, `value.list[0]`, `value["key with spaces"]`,
+///   `event.name`, `env.ANCYMON_NAME`
+/// - literals: integers, floats, `"strings"`, `'strings'`, `true`, `false`, `null`
+/// - comparisons: `==`, `!=`, `<`, `<=`, `>`, `>=`
+/// - logic: `&&`, `||`, `!` and parentheses
+///
+/// Missing paths evaluate to `null`. Values compare numerically when both are numbers,
+/// and ordering comparisons of incompatible values are false.
+/// Only environment variables prefixed with `ANCYMON_` are readable, so secrets in
+/// the process environment can't leak into messages.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Expression {
+    source: String,
+    node: Node,
+}
+impl Expression {
+    pub(crate) fn evaluate(&self, scope: &Scope) -> Value {
+        self.node.evaluate(scope)
+    }
+    /// Evaluate and check the result for truthiness.
+    pub(crate) fn matches(&self, scope: &Scope) -> bool {
+        truthy(&self.evaluate(scope))
+    }
+    /// Whether the expression reads the `event` root.
+    pub(crate) fn uses_event(&self) -> bool {
+        self.node.uses_event()
+    }
+}
+impl FromStr for Expression {
+    type Err = AncymonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |e: String| ConfigError::InvalidValue(format!("Invalid expression `{s}`: {e}"));
+        let tokens = tokenize(s).map_err(error)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let node = parser.expression().map_err(error)?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {token:?}")).into());
+        }
+        Ok(Self {
+            source: s.to_string(),
+            node,
+        })
+    }
+}
+impl TryFrom<String> for Expression {
+    type Error = AncymonError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Names available to expressions.
+pub(crate) struct Scope<'a> {
+    /// Event value, or the error message for `Err` inputs.
+    pub(crate) value: &'a Value,
+    pub(crate) event: &'a str,
+}
+impl Scope<'_> {
+    fn resolve(&self, root: Root, path: &[Segment]) -> Value {
+        match root {
+            Root::Value => resolve(self.value, path).cloned().unwrap_or_default(),
+            Root::Event => match path {
+                [] => Value::Map(HashMap::from_iter(vec![(
+                    "name".to_string(),
+                    Value::String(self.event.to_string()),
+                )])),
+                [Segment::Key(key)] if key == "name" => Value::String(self.event.to_string()),
+                _ => Value::Null,
+            },
+            Root::Env => match path {
+                [Segment::Key(key)] => std::env::var(key).map(Value::String).unwrap_or_default(),
+                _ => Value::Null,
+            },
+        }
+    }
+}
+
+const ENV_PREFIX: &str = "ANCYMON_";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Root {
+    Value,
+    Event,
+    Env,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Literal(Value),
+    Path(Root, Vec<Segment>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Comparison, Box<Node>, Box<Node>),
+}
+impl Node {
+    fn uses_event(&self) -> bool {
+        match self {
+            Self::Literal(_) => false,
+            Self::Path(root, _) => *root == Root::Event,
+            Self::Not(a) => a.uses_event(),
+            Self::And(a, b) | Self::Or(a, b) | Self::Compare(_, a, b) => {
+                a.uses_event() || b.uses_event()
+            }
+        }
+    }
+    fn evaluate(&self, scope: &Scope) -> Value {
+        match self {
+            Self::Literal(v) => v.clone(),
+            Self::Path(root, path) => scope.resolve(*root, path),
+            Self::Not(a) => Value::Bool(!truthy(&a.evaluate(scope))),
+            Self::And(a, b) => {
+                Value::Bool(truthy(&a.evaluate(scope)) && truthy(&b.evaluate(scope)))
+            }
+            Self::Or(a, b) => Value::Bool(truthy(&a.evaluate(scope)) || truthy(&b.evaluate(scope))),
+            Self::Compare(op, a, b) => {
+                let ordering = compare(&a.evaluate(scope), &b.evaluate(scope));
+                Value::Bool(match op {
+                    Comparison::Eq => ordering == Some(Ordering::Equal),
+                    Comparison::Ne => ordering != Some(Ordering::Equal),
+                    Comparison::Lt => ordering == Some(Ordering::Less),
+                    Comparison::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Comparison::Gt => ordering == Some(Ordering::Greater),
+                    Comparison::Ge => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                })
+            }
+        }
+    }
+}
+
+fn resolve<'a>(value: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        Segment::Key(key) => value.as_map()?.get(key),
+        Segment::Index(index) => value.as_array()?.get(*index),
+    })
+}
+
+pub(crate) fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Integer(i) => *i != 0,
+        Value::Float(f) => *f != 0.,
+        Value::String(s) => !s.is_empty(),
+        Value::Bytes(b) => !b.is_empty(),
+        Value::DateTime(_) => true,
+        Value::Array(a) => !a.is_empty(),
+        Value::Map(m) => !m.is_empty(),
+    }
+}
+
+/// `None` when the values can not be compared.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+        (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::DateTime(a), Value::DateTime(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Dot,
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    Not,
+    And,
+    Or,
+    Compare(Comparison),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, length) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('.', _) => (Token::Dot, 1),
+            ('[', _) => (Token::LeftBracket, 1),
+            (']', _) => (Token::RightBracket, 1),
+            ('(', _) => (Token::LeftParen, 1),
+            (')', _) => (Token::RightParen, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Compare(Comparison::Eq), 2),
+            ('!', Some('=')) => (Token::Compare(Comparison::Ne), 2),
+            ('<', Some('=')) => (Token::Compare(Comparison::Le), 2),
+            ('>', Some('=')) => (Token::Compare(Comparison::Ge), 2),
+            ('<', _) => (Token::Compare(Comparison::Lt), 1),
+            ('>', _) => (Token::Compare(Comparison::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('"' | '\'', _) => {
+                let mut string = String::new();
+                let mut end = i + 1;
+                loop {
+                    match chars.get(end) {
+                        None => return Err("unterminated string".to_string()),
+                        Some(&a) if a == c => break,
+                        Some('\\') => {
+                            let escaped = chars.get(end + 1).ok_or("unterminated string")?;
+                            string.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                a => *a,
+                            });
+                            end += 2;
+                        }
+                        Some(a) => {
+                            string.push(*a);
+                            end += 1;
+                        }
+                    }
+                }
+                (Token::String(string), end + 1 - i)
+            }
+            (c, _)
+                if c.is_ascii_digit() || (c == '-' && next.is_some_and(|a| a.is_ascii_digit())) =>
+            {
+                let mut end = i + 1;
+                while chars
+                    .get(end)
+                    .is_some_and(|a| a.is_ascii_digit() || *a == '.' || *a == '_')
+                {
+                    end += 1;
+                }
+                let number = chars[i..end]
+                    .iter()
+                    .filter(|a| **a != '_')
+                    .collect::<String>();
+                let token = if number.contains('.') {
+                    Token::Float(
+                        number
+                            .parse()
+                            .map_err(|_| format!("invalid number {number}"))?,
+                    )
+                } else {
+                    Token::Integer(
+                        number
+                            .parse()
+                            .map_err(|_| format!("invalid number {number}"))?,
+                    )
+                };
+                (token, end - i)
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let mut end = i + 1;
+                while chars
+                    .get(end)
+                    .is_some_and(|a| a.is_alphanumeric() || *a == '_' || *a == '-')
+                {
+                    end += 1;
+                }
+                (Token::Ident(chars[i..end].iter().collect()), end - i)
+            }
+            (c, _) => return Err(format!("unexpected character `{c}`")),
+        };
+        tokens.push(token);
+        i += length;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {expected:?}, found {token:?}")),
+            None => Err(format!("expected {expected:?}, found end of expression")),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+    fn not(&mut self) -> Result<Node, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+    fn comparison(&mut self) -> Result<Node, String> {
+        let node = self.operand()?;
+        if let Some(&Token::Compare(op)) = self.peek() {
+            self.next();
+            return Ok(Node::Compare(op, Box::new(node), Box::new(self.operand()?)));
+        }
+        Ok(node)
+    }
+    fn operand(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::LeftParen) => {
+                let node = self.expression()?;
+                self.expect(Token::RightParen)?;
+                Ok(node)
+            }
+            Some(Token::Integer(i)) => Ok(Node::Literal(Value::Integer(i))),
+            Some(Token::Float(f)) => Ok(Node::Literal(Value::Float(f))),
+            Some(Token::String(s)) => Ok(Node::Literal(Value::String(s))),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Node::Literal(Value::Bool(true))),
+                "false" => Ok(Node::Literal(Value::Bool(false))),
+                "null" => Ok(Node::Literal(Value::Null)),
+                "value" => self.path(Root::Value),
+                "event" => self.path(Root::Event),
+                "env" => match self.path(Root::Env)? {
+                    Node::Path(root, path) if matches!(path.as_slice(), [Segment::Key(key)] if key.starts_with(ENV_PREFIX)) => {
+                        Ok(Node::Path(root, path))
+                    }
+                    _ => Err(format!("only `env.{ENV_PREFIX}*` variables are readable")),
+                },
+                a => Err(format!(
+                    "unknown name `{a}`, paths start with `value`, `event` or `env`"
+                )),
+            },
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+    fn path(&mut self, root: Root) -> Result<Node, String> {
+        let mut path = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Dot) => {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Ident(key)) => path.push(Segment::Key(key)),
+                        _ => return Err("expected a key after `.`".to_string()),
+                    }
+                }
+                Some(Token::LeftBracket) => {
+                    self.next();
+                    match self.next() {
+                        Some(Token::Integer(i)) if i >= 0 => path.push(Segment::Index(i as usize)),
+                        Some(Token::String(key)) => path.push(Segment::Key(key)),
+                        _ => return Err("expected an index or a string key in `[]`".to_string()),
+                    }
+                    self.expect(Token::RightBracket)?;
+                }
+                _ => return Ok(Node::Path(root, path)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value() -> Value {
+        let map = |values: Vec<(&str, Value)>| {
+            Value::Map(HashMap::from_iter(
+                values.into_iter().map(|(k, v)| (k.to_string(), v)),
+            ))
+        };
+        map(vec![
+            ("temp", Value::Float(31.5)),
+            ("sensor", Value::String("attic".to_string())),
+            ("count", Value::Integer(3)),
+            ("enabled", Value::Bool(true)),
+            (
+                "readings",
+                Value::Array(vec![map(vec![("id", Value::Integer(7))])]),
+            ),
+            ("odd key", Value::Null),
+        ])
+    }
+
+    fn eval(s: &str) -> bool {
+        let value = value();
+        let scope = Scope {
+            value: &value,
+            event: "reading",
+        };
+        s.parse::<Expression>().unwrap().matches(&scope)
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(eval("value.temp > 30"));
+        assert!(eval("value.temp >= 31.5"));
+        assert!(!eval("value.temp < 30"));
+        assert!(eval("value.count == 3.0"));
+        assert!(eval("value.count != 4"));
+        assert!(eval("value.sensor == \"attic\""));
+        assert!(eval("value.sensor == 'attic'"));
+        assert!(eval("value.sensor < 'basement'"));
+        assert!(eval("value.readings[0].id == 7"));
+        assert!(eval("value[\"odd key\"] == null"));
+        assert!(eval("-1 < value.count"));
+    }
+
+    #[test]
+    fn logic() {
+        assert!(eval("value.temp > 30 && value.sensor == \"attic\""));
+        assert!(!eval("value.temp > 30 && value.sensor == \"garage\""));
+        assert!(eval("value.temp > 40 || value.enabled"));
+        assert!(eval("!(value.temp > 40)"));
+        assert!(eval("!value.missing && value.enabled"));
+        assert!(eval("value.count > 5 || value.count < 4 && value.enabled"));
+    }
+
+    #[test]
+    fn event() {
+        assert!(eval("event.name == 'reading'"));
+        assert!(eval("event.name != value.sensor"));
+        assert!(!eval("event.missing"));
+    }
+
+    #[test]
+    fn env() {
+        // SAFETY: no other test reads or writes this variable.
+        unsafe { std::env::set_var("ANCYMON_TEST_EXPRESSION", "set") };
+        assert!(eval("env.ANCYMON_TEST_EXPRESSION == 'set'"));
+        assert!(!eval("env.ANCYMON_UNDEFINED_VARIABLE"));
+    }
+
+    #[test]
+    fn missing_and_incompatible() {
+        assert!(!eval("value.missing.deeper > 1"));
+        assert!(eval("value.missing == null"));
+        assert!(!eval("value.sensor > 1"));
+        assert!(!eval("value.readings[5]"));
+        assert!(!eval("value.temp.nested"));
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "value.temp >",
+            "value.temp > 30 &&",
+            "(value.temp",
+            "temp > 30",
+            "value.temp = 30",
+            "'unterminated",
+            "value[-1]",
+            "value.temp > 30 30",
+            // Only prefixed variables are readable, not eg. secrets.
+            "env.PATH",
+            "env",
+            "env.ANCYMON_A.b",
+        ] {
+            assert!(s.parse::<Expression>().is_err(), "{s}");
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use serenity::{
+    all::{ChannelId, MessageId},
+    builder::{CreateEmbed, CreateEmbedFooter, CreateMessage},
+    http::{Http, HttpBuilder},
+};
+use std::sync::Arc;
+
+use crate::{
+    errors::{AncymonError, BuildError, ConfigError, RuntimeError},
+    events::EventValue,
+    handlers::{EventHandler, HandlerBuilder},
+    values::Value,
+};
+
+#[derive(Debug, Default, Deserialize)]
+struct DiscordConfig {
+    token: String,
+    channel: Option<u64>,
+    /// Alternative base url for the Discord API (eg. a ratelimiting proxy).
+    #[serde(rename = "api-url")]
+    api_url: Option<String>,
+}
+
+#[derive(Default)]
+pub struct DiscordHandler {
+    config: DiscordConfig,
+    http: Option<Http>,
+}
+#[async_trait]
+impl EventHandler for DiscordHandler {
+    async fn init(&mut self, config: &toml::Table) -> Result<(), AncymonError> {
+        self.config = config.clone().try_into().map_err(|e| BuildError::Handler {
+            message: "Invalid discord handler config".to_string(),
+            source: Some(Arc::new(e)),
+        })?;
+
+        let mut builder = HttpBuilder::new(&self.config.token);
+        if let Some(url) = &self.config.api_url {
+            builder = builder.proxy(url).ratelimiter_disabled(true);
+        }
+        self.http = Some(builder.build());
+        Ok(())
+    }
+    async fn execute(&self, event: &Value, arguments: &Value) -> EventValue {
+        let arguments: DiscordArguments = arguments.clone().try_into()?;
+        let http = self.http.as_ref().ok_or(RuntimeError::Handler {
+            message: "Discord handler not initialized".to_string(),
+            source: None,
+        })?;
+
+        let channel =
+            arguments
+                .channel
+                .or(self.config.channel)
+                .ok_or(ConfigError::MissingValue(
+                    "Discord channel is not specified".to_string(),
+                ))?;
+        let channel = ChannelId::new(channel);
+
+        let mut message = match arguments.format {
+            MessageFormat::Text => CreateMessage::new().content(render_text(event)),
+            MessageFormat::Pretty => {
+                CreateMessage::new().content(format!("```\n{}\n```", event.pretty()))
+            }
+            MessageFormat::Embed => CreateMessage::new().embed(build_embed(event)?),
+        };
+        if let Some(reply_to) = arguments.reply_to {
+            message = message.reference_message((channel, MessageId::new(reply_to)));
+        }
+
+        let message =
+            channel
+                .send_message(http, message)
+                .await
+                .map_err(|e| RuntimeError::Handler {
+                    message: "Discord message failed".to_string(),
+                    source: Some(Arc::new(e)),
+                })?;
+
+        Ok(Value::Integer(message.id.get() as i64))
+    }
+}
+
+pub struct DiscordBuilder;
+impl HandlerBuilder for DiscordBuilder {
+    fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError> {
+        Ok(Box::new(DiscordHandler::default()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum MessageFormat {
+    #[default]
+    Text,
+    Pretty,
+    Embed,
+}
+
+struct DiscordArguments {
+    channel: Option<u64>,
+    format: MessageFormat,
+    reply_to: Option<u64>,
+}
+impl TryFrom<Value> for DiscordArguments {
+    type Error = AncymonError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        // Allow empty arguments, eg. `arguments = []`
+        let empty = Default::default();
+        let map = match &value {
+            Value::Null => &empty,
+            Value::Array(a) if a.is_empty() => &empty,
+            v => v
+                .as_map()
+                .ok_or(ConfigError::InvalidValueType("Expected map".to_string()))?,
+        };
+
+        let channel = map.get("channel").map(parse_id).transpose()?;
+        let reply_to = map.get("reply-to").map(parse_id).transpose()?;
+        let format = if let Some(v) = map.get("format") {
+            match v
+                .as_str()
+                .ok_or(ConfigError::InvalidValueType("Expected string".to_string()))?
+            {
+                "text" => MessageFormat::Text,
+                "pretty" => MessageFormat::Pretty,
+                "embed" => MessageFormat::Embed,
+                a => {
+                    return Err(
+                        ConfigError::InvalidValue(format!("Unknown message format: {a}")).into(),
+                    );
+                }
+            }
+        } else {
+            MessageFormat::default()
+        };
+
+        Ok(Self {
+            channel,
+            format,
+            reply_to,
+        })
+    }
+}
+
+/// Discord ids are non zero snowflakes, given either as integers or strings.
+fn parse_id(value: &Value) -> Result<u64, AncymonError> {
+    let id = match value {
+        Value::Integer(i) => u64::try_from(*i).ok(),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        _ => {
+            return Err(
+                ConfigError::InvalidValueType("Expected integer or string id".to_string()).into(),
+            );
+        }
+    };
+    match id {
+        Some(id) if id > 0 => Ok(id),
+        _ => Err(ConfigError::InvalidValue(format!("Invalid Discord id: {value:?}")).into()),
+    }
+}
+
+fn render_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Array(_) | Value::Map(_) => value.pretty(),
+        Value::Null => String::new(),
+        v => v.pretty(),
+    }
+}
+
+fn build_embed(value: &Value) -> Result<CreateEmbed, AncymonError> {
+    let map = value.as_map().ok_or(RuntimeError::InvalidArgumentType(
+        "Discord embed requires a map value".to_string(),
+    ))?;
+    let mut embed = CreateEmbed::new();
+
+    if let Some(v) = map.get("title") {
+        embed = embed.title(render_text(v));
+    }
+    if let Some(v) = map.get("description") {
+        embed = embed.description(render_text(v));
+    }
+    if let Some(v) = map.get("url") {
+        embed = embed.url(render_text(v));
+    }
+    if let Some(v) = map.get("image") {
+        embed = embed.image(render_text(v));
+    }
+    if let Some(v) = map.get("thumbnail") {
+        embed = embed.thumbnail(render_text(v));
+    }
+    if let Some(v) = map.get("footer") {
+        embed = embed.footer(CreateEmbedFooter::new(render_text(v)));
+    }
+    if let Some(v) = map.get("color") {
+        let color = v.as_int().and_then(|a| u32::try_from(a).ok()).ok_or(
+            RuntimeError::InvalidArgumentType(
+                "Discord embed color has to be a positive integer".to_string(),
+            ),
+        )?;
+        embed = embed.color(color);
+    }
+
+    match map.get("fields") {
+        None => (),
+        Some(Value::Map(fields)) => {
+            // Maps are unordered, keep the output stable.
+            let mut fields = fields.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|a| a.0);
+            embed = embed.fields(fields.into_iter().map(|(k, v)| (k, render_text(v), true)));
+        }
+        Some(Value::Array(fields)) => {
+            for field in fields {
+                let field = field.as_map().ok_or(RuntimeError::InvalidArgumentType(
+                    "Discord embed field has to be a map".to_string(),
+                ))?;
+                let name = field.get("name").map(render_text).unwrap_or_default();
+                let value = field.get("value").map(render_text).unwrap_or_default();
+                let inline = field
+                    .get("inline")
+                    .and_then(|a| a.as_bool())
+                    .unwrap_or(false);
+                embed = embed.field(name, value, inline);
+            }
+        }
+        Some(_) => {
+            return Err(RuntimeError::InvalidArgumentType(
+                "Discord embed fields have to be a map or an array".to_string(),
+            )
+            .into());
+        }
+    }
+
+    Ok(embed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{extract::State, routing::post, Json, Router};
+
+    use super::*;
+
+    type Requests = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    /// Local stand-in for the Discord REST API. Records the posted messages.
+    async fn api() -> (String, Requests) {
+        async fn create_message(
+            State(requests): State<Requests>,
+            axum::extract::Path(channel): axum::extract::Path<String>,
+            Json(body): Json<serde_json::Value>,
+        ) -> Json<serde_json::Value> {
+            requests.lock().unwrap().push((channel.clone(), body));
+            Json(serde_json::json!({
+                "id": "42",
+                "channel_id": channel,
+                "author": { "id": "7", "username": "ancymon", "global_name": null, "avatar": null },
+                "content": "",
+                "timestamp": "2026-01-01T00:00:00Z",
+                "edited_timestamp": null,
+                "tts": false,
+                "mention_everyone": false,
+                "mentions": [],
+                "mention_roles": [],
+                "attachments": [],
+                "embeds": [],
+                "pinned": false,
+                "type": 0
+            }))
+        }
+
+        let requests = Requests::default();
+        let app = Router::new()
+            .route("/api/v10/channels/{channel}/messages", post(create_message))
+            .with_state(requests.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, requests)
+    }
+
+    async fn handler(url: &str) -> DiscordHandler {
+        let config = toml::Table::from_str(&format!(
+            "token = \"test\"\nchannel = 3\napi-url = \"{url}\""
+        ))
+        .unwrap();
+        let mut handler = DiscordHandler::default();
+        handler.init(&config).await.unwrap();
+        handler
+    }
+
+    fn arguments(values: Vec<(&str, Value)>) -> Value {
+        Value::Map(HashMap::from_iter(
+            values.into_iter().map(|(k, v)| (k.to_string(), v)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn send_text() {
+        let (url, requests) = api().await;
+        let handler = handler(&url).await;
+
+        let result = handler
+            .execute(&Value::String("hello".to_string()), &Value::Array(vec![]))
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Integer(42));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].0, "3");
+        assert_eq!(requests[0].1["content"], "hello");
+    }
+
+    #[tokio::test]
+    async fn send_pretty_reply() {
+        let (url, requests) = api().await;
+        let handler = handler(&url).await;
+
+        handler
+            .execute(
+                &Value::Array(vec![Value::Integer(1)]),
+                &arguments(vec![
+                    ("channel", Value::Integer(5)),
+                    ("format", Value::String("pretty".to_string())),
+                    ("reply-to", Value::String("11".to_string())),
+                ]),
+            )
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].0, "5");
+        assert_eq!(requests[0].1["content"], "```\n[\n  1,\n]\n```");
+        assert_eq!(requests[0].1["message_reference"]["message_id"], "11");
+    }
+
+    #[tokio::test]
+    async fn send_embed() {
+        let (url, requests) = api().await;
+        let handler = handler(&url).await;
+
+        let event = arguments(vec![
+            ("title", Value::String("Attic".to_string())),
+            ("color", Value::Integer(0xff0000)),
+            (
+                "fields",
+                arguments(vec![
+                    ("temp", Value::Float(23.5)),
+                    ("humidity", Value::Integer(40)),
+                ]),
+            ),
+        ]);
+        handler
+            .execute(
+                &event,
+                &arguments(vec![("format", Value::String("embed".to_string()))]),
+            )
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        let embed = &requests[0].1["embeds"][0];
+        assert_eq!(embed["title"], "Attic");
+        assert_eq!(embed["color"], 0xff0000);
+        assert_eq!(embed["fields"][0]["name"], "humidity");
+        assert_eq!(embed["fields"][1]["value"], "23.5");
+    }
+
+    #[tokio::test]
+    async fn embed_requires_map() {
+        let (url, _) = api().await;
+        let handler = handler(&url).await;
+
+        let result = handler
+            .execute(
+                &Value::Integer(1),
+                &arguments(vec![("format", Value::String("embed".to_string()))]),
+            )
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,471 @@
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Client, Method,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    config::deserialize_duration,
+    errors::{AncymonError, BuildError, ConfigError, RuntimeError},
+    events::EventValue,
+    handlers::{EventHandler, HandlerBuilder},
+    values::Value,
+};
+
+#[derive(Debug, Default, Deserialize)]
+struct HttpConfig {
+    /// Prefix for relative action urls.
+    #[serde(rename = "base-url")]
+    base_url: Option<String>,
+    /// Headers sent with every request.
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    timeout: Option<Duration>,
+}
+
+/// Sends an HTTP request and returns the response as a map with
+/// `status`, `headers` and `body`. JSON bodies are parsed into values.
+#[derive(Default)]
+pub struct HttpHandler {
+    config: HttpConfig,
+    client: Option<Client>,
+}
+#[async_trait]
+impl EventHandler for HttpHandler {
+    async fn init(&mut self, config: &toml::Table) -> Result<(), AncymonError> {
+        self.config = config.clone().try_into().map_err(|e| BuildError::Handler {
+            message: "Invalid http handler config".to_string(),
+            source: Some(Arc::new(e)),
+        })?;
+
+        let mut builder = Client::builder().default_headers(header_map(&self.config.headers)?);
+        if let Some(timeout) = self.config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        self.client = Some(builder.build().map_err(|e| BuildError::Handler {
+            message: "HTTP client failed".to_string(),
+            source: Some(Arc::new(e)),
+        })?);
+        Ok(())
+    }
+    async fn execute(&self, event: &Value, arguments: &Value) -> EventValue {
+        let arguments: HttpArguments = arguments.clone().try_into()?;
+        let client = self.client.as_ref().ok_or(RuntimeError::Handler {
+            message: "HTTP handler not initialized".to_string(),
+            source: None,
+        })?;
+
+        let url = match &self.config.base_url {
+            Some(base) if !arguments.url.contains("://") => format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                arguments.url.trim_start_matches('/')
+            ),
+            _ => arguments.url.clone(),
+        };
+        let mut request = client
+            .request(arguments.method.clone(), url)
+            .headers(header_map(&arguments.headers)?);
+        if !arguments.query.is_empty() {
+            request = request.query(&arguments.query);
+        }
+
+        // Without an explicit body, POST and PUT send the event value.
+        let body = match &arguments.body {
+            Some(body) => Some(body),
+            None if matches!(arguments.method, Method::POST | Method::PUT) && !event.is_null() => {
+                Some(event)
+            }
+            None => None,
+        };
+        request = match body {
+            Some(Value::String(s)) => request.body(s.clone()),
+            Some(Value::Bytes(b)) => request.body(b.clone()),
+            Some(v) => request.json(&serde_json::Value::from(v)),
+            None => request,
+        };
+
+        let response = request.send().await.map_err(|e| RuntimeError::Handler {
+            message: "HTTP request failed".to_string(),
+            source: Some(Arc::new(e)),
+        })?;
+
+        let status = response.status();
+        let headers = response.headers().iter().fold(
+            HashMap::<String, Value>::new(),
+            |mut headers, (k, v)| {
+                let v = String::from_utf8_lossy(v.as_bytes()).to_string();
+                headers
+                    .entry(k.to_string())
+                    .and_modify(|a| {
+                        if let Value::String(a) = a {
+                            a.push_str(", ");
+                            a.push_str(&v);
+                        }
+                    })
+                    .or_insert(Value::String(v));
+                headers
+            },
+        );
+        let json = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|a| a.to_str().ok())
+            .is_some_and(|a| a.contains("json"));
+        let bytes = response.bytes().await.map_err(|e| RuntimeError::Handler {
+            message: "HTTP response failed".to_string(),
+            source: Some(Arc::new(e)),
+        })?;
+        let body = parse_body(&bytes, json);
+
+        if arguments.error_for_status && (status.is_client_error() || status.is_server_error()) {
+            return Err(RuntimeError::Handler {
+                message: format!(
+                    "HTTP request failed with status {status}: {}",
+                    body.pretty()
+                ),
+                source: None,
+            }
+            .into());
+        }
+
+        Ok(Value::Map(HashMap::from_iter(vec![
+            ("status".to_string(), Value::Integer(status.as_u16() as i64)),
+            ("headers".to_string(), Value::Map(headers)),
+            ("body".to_string(), body),
+        ])))
+    }
+}
+
+pub struct HttpBuilder;
+impl HandlerBuilder for HttpBuilder {
+    fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError> {
+        Ok(Box::new(HttpHandler::default()))
+    }
+}
+
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, AncymonError> {
+    headers
+        .iter()
+        .map(|(k, v)| {
+            let name = HeaderName::try_from(k)
+                .map_err(|e| ConfigError::InvalidValue(format!("Invalid header name {k}: {e}")))?;
+            let value = HeaderValue::try_from(v)
+                .map_err(|e| ConfigError::InvalidValue(format!("Invalid header {k} value: {e}")))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Parse JSON bodies when possible, falling back to text and raw bytes.
+fn parse_body(bytes: &[u8], json: bool) -> Value {
+    if bytes.is_empty() {
+        return Value::Null;
+    }
+    if json && let Ok(v) = serde_json::from_slice::<serde_json::Value>(bytes) {
+        return v.into();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Value::String(s.to_string()),
+        Err(_) => Value::Bytes(bytes.to_vec()),
+    }
+}
+
+struct HttpArguments {
+    method: Method,
+    url: String,
+    headers: HashMap<String, String>,
+    query: Vec<(String, String)>,
+    body: Option<Value>,
+    /// Return an error for 4xx and 5xx responses.
+    error_for_status: bool,
+}
+impl TryFrom<Value> for HttpArguments {
+    type Error = AncymonError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let map = value
+            .as_map()
+            .ok_or(ConfigError::InvalidValueType("Expected map".to_string()))?;
+
+        let url = map
+            .get("url")
+            .ok_or(ConfigError::MissingValue(
+                "Field `url` is required".to_string(),
+            ))?
+            .as_str()
+            .ok_or(ConfigError::InvalidValueType("Expected string".to_string()))?
+            .to_string();
+        let method = if let Some(v) = map.get("method") {
+            match v
+                .as_str()
+                .ok_or(ConfigError::InvalidValueType("Expected string".to_string()))?
+                .to_uppercase()
+                .as_str()
+            {
+                "GET" => Method::GET,
+                "POST" => Method::POST,
+                "PUT" => Method::PUT,
+                "DELETE" => Method::DELETE,
+                a => {
+                    return Err(
+                        ConfigError::InvalidValue(format!("Unsupported HTTP method: {a}")).into(),
+                    );
+                }
+            }
+        } else {
+            Method::GET
+        };
+        let headers = string_map(map, "headers")?.into_iter().collect();
+        let query = string_map(map, "query")?;
+        let error_for_status = if let Some(v) = map.get("error-for-status") {
+            v.as_bool()
+                .ok_or(ConfigError::InvalidValueType("Expected bool".to_string()))?
+        } else {
+            true
+        };
+
+        Ok(Self {
+            method,
+            url,
+            headers,
+            query,
+            body: map.get("body").cloned(),
+            error_for_status,
+        })
+    }
+}
+
+/// Map of scalars rendered as strings, sorted by key.
+fn string_map(
+    map: &HashMap<String, Value>,
+    key: &str,
+) -> Result<Vec<(String, String)>, AncymonError> {
+    let Some(v) = map.get(key) else {
+        return Ok(Vec::new());
+    };
+    let mut entries = v
+        .as_map()
+        .ok_or(ConfigError::InvalidValueType(format!(
+            "Expected map for `{key}`"
+        )))?
+        .iter()
+        .map(|(k, v)| {
+            let v = match v {
+                Value::String(s) => s.clone(),
+                Value::Integer(i) => i.to_string(),
+                Value::Float(f) => f.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => {
+                    return Err(ConfigError::InvalidValueType(format!(
+                        "Expected scalar values for `{key}`"
+                    ))
+                    .into());
+                }
+            };
+            Ok((k.clone(), v))
+        })
+        .collect::<Result<Vec<_>, AncymonError>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        extract::{Query, State},
+        http::{HeaderMap as AxumHeaders, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Request {
+        headers: HashMap<String, String>,
+        query: HashMap<String, String>,
+        body: String,
+    }
+    type Requests = Arc<Mutex<Vec<Request>>>;
+
+    /// Local mock server, records the received requests.
+    async fn server() -> (String, Requests) {
+        async fn echo(
+            State(requests): State<Requests>,
+            headers: AxumHeaders,
+            Query(query): Query<HashMap<String, String>>,
+            body: String,
+        ) -> Json<serde_json::Value> {
+            requests.lock().unwrap().push(Request {
+                headers: headers
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
+                    .collect(),
+                query,
+                body: body.clone(),
+            });
+            Json(serde_json::json!({ "ok": true, "received": body }))
+        }
+        async fn text() -> &'static str {
+            "plain"
+        }
+        async fn fail() -> (StatusCode, &'static str) {
+            (StatusCode::SERVICE_UNAVAILABLE, "down")
+        }
+
+        let requests = Requests::default();
+        let app = Router::new()
+            .route("/echo", post(echo).put(echo).get(echo).delete(echo))
+            .route("/text", get(text))
+            .route("/fail", get(fail))
+            .with_state(requests.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, requests)
+    }
+
+    async fn handler(url: &str) -> HttpHandler {
+        let config = toml::Table::from_str(&format!(
+            "base-url = \"{url}\"\nheaders = {{ x-token = \"secret\" }}\ntimeout = \"5s\""
+        ))
+        .unwrap();
+        let mut handler = HttpHandler::default();
+        handler.init(&config).await.unwrap();
+        handler
+    }
+
+    fn arguments(s: &str) -> Value {
+        toml::from_str(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn post_event() {
+        let (url, requests) = server().await;
+        let handler = handler(&url).await;
+        let event = Value::Map(HashMap::from_iter(vec![(
+            "temp".to_string(),
+            Value::Float(23.5),
+        )]));
+
+        let result = handler
+            .execute(
+                &event,
+                &arguments(
+                    "method = \"post\"\nurl = \"/echo\"\nheaders = { x-sensor = \"attic\" }",
+                ),
+            )
+            .await
+            .unwrap();
+        let result = result.as_map().unwrap();
+        assert_eq!(result["status"], Value::Integer(200));
+        assert_eq!(
+            result["headers"].as_map().unwrap()["content-type"],
+            Value::String("application/json".to_string())
+        );
+        assert_eq!(result["body"].as_map().unwrap()["ok"], Value::Bool(true));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].body, r#"{"temp":23.5}"#);
+        assert_eq!(requests[0].headers["x-token"], "secret");
+        assert_eq!(requests[0].headers["x-sensor"], "attic");
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+    }
+
+    #[tokio::test]
+    async fn get_query() {
+        let (url, requests) = server().await;
+        let handler = handler(&url).await;
+
+        handler
+            .execute(
+                &Value::Integer(1),
+                &arguments(&format!(
+                    "url = \"{url}/echo\"\nquery = {{ sensor = \"attic\", limit = 5 }}"
+                )),
+            )
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].query["sensor"], "attic");
+        assert_eq!(requests[0].query["limit"], "5");
+        assert_eq!(requests[0].body, "");
+    }
+
+    #[tokio::test]
+    async fn explicit_body() {
+        let (url, requests) = server().await;
+        let handler = handler(&url).await;
+
+        handler
+            .execute(
+                &Value::Integer(1),
+                &arguments("method = \"PUT\"\nurl = \"echo\"\nbody = \"hello\""),
+            )
+            .await
+            .unwrap();
+        assert_eq!(requests.lock().unwrap()[0].body, "hello");
+    }
+
+    #[tokio::test]
+    async fn text_response() {
+        let (url, _) = server().await;
+        let handler = handler(&url).await;
+
+        let result = handler
+            .execute(&Value::Null, &arguments("url = \"/text\""))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.as_map().unwrap()["body"],
+            Value::String("plain".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let (url, _) = server().await;
+        let handler = handler(&url).await;
+
+        let result = handler
+            .execute(&Value::Null, &arguments("url = \"/fail\""))
+            .await;
+        assert!(result.is_err());
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &arguments("url = \"/fail\"\nerror-for-status = false"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.as_map().unwrap()["status"], Value::Integer(503));
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments() {
+        let (url, _) = server().await;
+        let handler = handler(&url).await;
+
+        for arguments_str in [
+            "method = \"GET\"",
+            "url = \"/echo\"\nmethod = \"TRACE\"",
+            "url = \"/echo\"\nheaders = { a = [1] }",
+        ] {
+            let result = handler
+                .execute(&Value::Null, &arguments(arguments_str))
+                .await;
+            assert!(result.is_err(), "{arguments_str}");
+        }
+    }
+}
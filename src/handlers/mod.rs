@@ -3,7 +3,9 @@ use async_trait::async_trait;
 use crate::{errors::AncymonError, events::EventValue, values::Value};
 
 pub mod discord;
+pub mod http;
 pub mod sql;
+pub mod transform;
 
 pub trait HandlerBuilder {
     fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError>;
@@ -11,10 +13,16 @@ pub trait HandlerBuilder {
 
 #[async_trait]
 pub trait EventHandler {
+    #[allow(unused_variables)]
     async fn init(&mut self, config: &toml::Table) -> Result<(), AncymonError> {
         Ok(())
     }
     async fn execute(&self, event: &Value, arguments: &Value) -> EventValue;
+    /// Argument keys, at any depth, that must not contain templates,
+    /// eg. SQL queries, where event data has to be bound instead.
+    fn literal_arguments(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 pub struct DebugHandler;
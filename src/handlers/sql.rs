@@ -1,29 +1,77 @@
 use async_trait::async_trait;
+use chrono::DateTime;
 use serde::Deserialize;
 use sqlx::{
-    any::{install_default_drivers, AnyArguments, AnyRow, AnyTypeInfoKind},
+    any::{
+        install_default_drivers, AnyArguments, AnyPoolOptions, AnyQueryResult, AnyRow,
+        AnyTypeInfoKind,
+    },
     query::Query,
-    Any, AnyConnection, Column, Connection, Row,
+    Any, AnyConnection, AnyPool, Column, Row,
 };
+use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Duration};
 
 use crate::{
+    config::deserialize_duration,
     errors::{AncymonError, BuildError, ConfigError, RuntimeError},
     events::EventValue,
     handlers::{EventHandler, HandlerBuilder},
-    values::Value,
+    values::{parse_datetime, Value},
 };
 
 #[derive(Debug, Default, Deserialize)]
 struct SqlConfig {
     #[serde(rename = "connection-string")]
     connection_string: String,
+    #[serde(rename = "pool-size")]
+    pool_size: Option<u32>,
+    /// Connections kept open even when idle, eg. to preserve in-memory databases.
+    #[serde(rename = "min-connections")]
+    min_connections: Option<u32>,
+    #[serde(
+        rename = "idle-timeout",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    idle_timeout: Option<Duration>,
+    #[serde(
+        rename = "acquire-timeout",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    acquire_timeout: Option<Duration>,
 }
 
 #[derive(Default)]
 pub struct SqlHandler {
     config: SqlConfig,
+    pool: Option<AnyPool>,
 }
 impl SqlHandler {
+    /// The pool connects lazily, so the bot starts, and reloads, while the database
+    /// is unreachable. Executions fail with a handler error until it is back.
+    fn connect(&self) -> Result<AnyPool, AncymonError> {
+        let mut options = AnyPoolOptions::new();
+        if let Some(size) = self.config.pool_size {
+            options = options.max_connections(size);
+        }
+        if let Some(min) = self.config.min_connections {
+            options = options.min_connections(min);
+        }
+        if let Some(timeout) = self.config.idle_timeout {
+            options = options.idle_timeout(timeout);
+        }
+        if let Some(timeout) = self.config.acquire_timeout {
+            options = options.acquire_timeout(timeout);
+        }
+        let pool = options
+            .connect_lazy(&self.config.connection_string)
+            .map_err(|e| BuildError::Handler {
+                message: "Invalid sql connection string".to_string(),
+                source: Some(Arc::new(e)),
+            })?;
+        Ok(pool)
+    }
     async fn fetch_one<'a>(
         &self,
         connection: &mut AnyConnection,
@@ -32,7 +80,10 @@ impl SqlHandler {
         let row = query
             .fetch_one(connection)
             .await
-            .map_err(|e| RuntimeError::Handler(format!("Sql fetch one failed {e}")))?;
+            .map_err(|e| RuntimeError::Handler {
+                message: "Sql fetch one failed".to_string(),
+                source: Some(Arc::new(e)),
+            })?;
         Ok(row)
     }
     async fn fetch_many<'a>(
@@ -43,41 +94,138 @@ impl SqlHandler {
         let rows = query
             .fetch_all(connection)
             .await
-            .map_err(|e| RuntimeError::Handler(format!("Sql fetch many failed {e}")))?;
+            .map_err(|e| RuntimeError::Handler {
+                message: "Sql fetch many failed".to_string(),
+                source: Some(Arc::new(e)),
+            })?;
         Ok(rows)
     }
+    async fn execute_query<'a>(
+        &self,
+        connection: &mut AnyConnection,
+        sql: &str,
+        query: Query<'a, Any, AnyArguments<'a>>,
+    ) -> Result<Value, AncymonError> {
+        let result = query
+            .execute(&mut *connection)
+            .await
+            .map_err(|e| RuntimeError::Handler {
+                message: "Sql execute failed".to_string(),
+                source: Some(Arc::new(e)),
+            })?;
+
+        // Other statements would report the id of an earlier insert on the same connection.
+        if !is_insert(sql) {
+            return Ok(map_query_result(&result, None));
+        }
+        // The Any driver does not forward the inserted row id for SQLite. The lookup runs
+        // on the connection, or transaction, of the statement, so it sees its own insert.
+        let last_insert_id = match result.last_insert_id() {
+            Some(id) => Some(id),
+            None if connection.backend_name() == "SQLite" && result.rows_affected() > 0 => {
+                sqlx::query_scalar::<_, i64>("SELECT last_insert_rowid()")
+                    .fetch_optional(&mut *connection)
+                    .await
+                    .map_err(|e| RuntimeError::Handler {
+                        message: "Sql execute failed".to_string(),
+                        source: Some(Arc::new(e)),
+                    })?
+            }
+            None => None,
+        };
+        Ok(map_query_result(&result, last_insert_id))
+    }
+    async fn run_statement(
+        &self,
+        connection: &mut AnyConnection,
+        arguments: &SqlArguments,
+        event: &Value,
+    ) -> EventValue {
+        let placeholder = Placeholder::from_connection_string(&self.config.connection_string);
+        let (sql, binds) = arguments.statement(event, placeholder)?;
+        let mut query = sqlx::query(sql.as_ref());
+        for value in binds {
+            query = bind_value(query, value)?;
+        }
+
+        match arguments.mode {
+            QueryMode::FetchOne => {
+                let row = self.fetch_one(connection, query).await?;
+                map_row(&row, arguments)
+            }
+            QueryMode::FetchMany => {
+                let rows = self.fetch_many(connection, query).await?;
+                let values = rows
+                    .iter()
+                    .map(|a| map_row(a, arguments))
+                    .collect::<Result<Vec<_>, AncymonError>>()?;
+                Ok(Value::Array(values))
+            }
+            QueryMode::Execute => self.execute_query(connection, &sql, query).await,
+        }
+    }
 }
 #[async_trait]
 impl EventHandler for SqlHandler {
     async fn init(&mut self, config: &toml::Table) -> Result<(), AncymonError> {
         install_default_drivers();
-        self.config = config
-            .clone()
-            .try_into()
-            .map_err(|e| BuildError::Handler(format!("{e}")))?;
+        self.config = config.clone().try_into().map_err(|e| BuildError::Handler {
+            message: "Invalid sql handler config".to_string(),
+            source: Some(Arc::new(e)),
+        })?;
+        self.pool = Some(self.connect()?);
         Ok(())
     }
     async fn execute(&self, event: &Value, arguments: &Value) -> EventValue {
-        let arguments: SqlArguments = arguments.clone().try_into()?;
+        let script: SqlScript = arguments.clone().try_into()?;
+        let pool = self.pool.as_ref().ok_or(RuntimeError::Handler {
+            message: "Sql handler not initialized".to_string(),
+            source: None,
+        })?;
 
-        let mut connection = AnyConnection::connect(&self.config.connection_string)
-            .await
-            .map_err(|e| RuntimeError::Handler(format!("Sql connection failed:{e}")))?;
+        match script {
+            SqlScript::Single(arguments) => {
+                let mut connection = pool.acquire().await.map_err(|e| RuntimeError::Handler {
+                    message: "Sql connection failed".to_string(),
+                    source: Some(Arc::new(e)),
+                })?;
+                self.run_statement(&mut connection, &arguments, event).await
+            }
+            SqlScript::Transaction(statements) => {
+                let mut transaction = pool.begin().await.map_err(|e| RuntimeError::Handler {
+                    message: "Sql transaction failed".to_string(),
+                    source: Some(Arc::new(e)),
+                })?;
 
-        let query = sqlx::query(&arguments.query).bind(2);
+                let mut results = Vec::with_capacity(statements.len());
+                for arguments in statements.iter() {
+                    match self.run_statement(&mut transaction, arguments, event).await {
+                        Ok(value) => results.push(value),
+                        Err(e) => {
+                            if let Err(rollback) = transaction.rollback().await {
+                                tracing::error!("Sql rollback failed: {rollback}");
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
 
-        if arguments.fetch_many {
-            let rows = self.fetch_many(&mut connection, query).await?;
-            Ok(Value::Array(
-                rows.iter()
-                    .map(map_row)
-                    .collect::<Result<Vec<_>, AncymonError>>()?,
-            ))
-        } else {
-            let row = self.fetch_one(&mut connection, query).await?;
-            map_row(&row)
+                transaction
+                    .commit()
+                    .await
+                    .map_err(|e| RuntimeError::Handler {
+                        message: "Sql commit failed".to_string(),
+                        source: Some(Arc::new(e)),
+                    })?;
+                Ok(Value::Array(results))
+            }
         }
     }
+    /// Event data only reaches queries through `bind-one` and `bind-many`, so it can not
+    /// inject SQL.
+    fn literal_arguments(&self) -> &'static [&'static str] {
+        &["query"]
+    }
 }
 
 pub struct SqlBuilder;
@@ -87,9 +235,61 @@ impl HandlerBuilder for SqlBuilder {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum QueryMode {
+    #[default]
+    FetchOne,
+    FetchMany,
+    /// Statements not returning rows, eg. INSERT or UPDATE
+    Execute,
+}
+
+/// Shape of a single fetched row.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum RowFormat {
+    /// Scalar for single column rows, array otherwise.
+    #[default]
+    Auto,
+    Array,
+    /// Column name to value.
+    Map,
+}
+
+/// Either a single statement, or a list of `statements` run inside one transaction.
+enum SqlScript {
+    Single(SqlArguments),
+    Transaction(Vec<SqlArguments>),
+}
+impl TryFrom<Value> for SqlScript {
+    type Error = AncymonError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let Some(statements) = value.as_map().and_then(|a| a.get("statements")) else {
+            return Ok(Self::Single(value.try_into()?));
+        };
+        let statements = statements
+            .as_array()
+            .ok_or(ConfigError::InvalidValueType(
+                "Expected array for `statements`".to_string(),
+            ))?
+            .iter()
+            .map(|a| SqlArguments::try_from(a.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if statements.is_empty() {
+            return Err(
+                ConfigError::MissingValue("Field `statements` is empty".to_string()).into(),
+            );
+        }
+        Ok(Self::Transaction(statements))
+    }
+}
+
 struct SqlArguments {
     query: String,
-    fetch_many: bool,
+    mode: QueryMode,
+    row_format: RowFormat,
+    json_columns: Vec<String>,
+    datetime_columns: Vec<String>,
     bind_one: bool,
     bind_many: bool,
 }
@@ -116,6 +316,42 @@ impl TryFrom<Value> for SqlArguments {
         } else {
             false
         };
+        let execute = if let Some(v) = map.get("execute") {
+            v.as_bool()
+                .ok_or(ConfigError::InvalidValueType("Expected bool".to_string()))?
+        } else {
+            false
+        };
+        let mode = match (fetch_many, execute) {
+            (false, false) => QueryMode::FetchOne,
+            (true, false) => QueryMode::FetchMany,
+            (false, true) => QueryMode::Execute,
+            (true, true) => {
+                return Err(ConfigError::InvalidValue(
+                    "Fields `fetch-many` and `execute` are exclusive".to_string(),
+                )
+                .into());
+            }
+        };
+        let row_format = if let Some(v) = map.get("row-format") {
+            match v
+                .as_str()
+                .ok_or(ConfigError::InvalidValueType("Expected string".to_string()))?
+            {
+                "auto" => RowFormat::Auto,
+                "array" => RowFormat::Array,
+                "map" => RowFormat::Map,
+                a => {
+                    return Err(
+                        ConfigError::InvalidValue(format!("Unknown row format: {a}")).into(),
+                    );
+                }
+            }
+        } else {
+            RowFormat::default()
+        };
+        let json_columns = string_list(map, "json-columns")?;
+        let datetime_columns = string_list(map, "datetime-columns")?;
         let bind_one = if let Some(v) = map.get("bind-one") {
             v.as_bool()
                 .ok_or(ConfigError::InvalidValueType("Expected bool".to_string()))?
@@ -129,33 +365,293 @@ impl TryFrom<Value> for SqlArguments {
             false
         };
 
+        if bind_one && bind_many {
+            return Err(ConfigError::InvalidValue(
+                "Fields `bind-one` and `bind-many` are exclusive".to_string(),
+            )
+            .into());
+        }
+
         Ok(Self {
             query,
-            fetch_many,
+            mode,
+            row_format,
+            json_columns,
+            datetime_columns,
             bind_one,
             bind_many,
         })
     }
 }
+fn string_list(map: &HashMap<String, Value>, key: &str) -> Result<Vec<String>, AncymonError> {
+    let Some(v) = map.get(key) else {
+        return Ok(Vec::new());
+    };
+    v.as_array()
+        .ok_or(ConfigError::InvalidValueType(format!(
+            "Expected array of strings for `{key}`"
+        )))?
+        .iter()
+        .map(|a| {
+            a.as_str().map(|a| a.to_string()).ok_or(
+                ConfigError::InvalidValueType(format!("Expected array of strings for `{key}`"))
+                    .into(),
+            )
+        })
+        .collect()
+}
+
+impl SqlArguments {
+    /// Return the query to run together with the values to bind, in order.
+    /// Maps are bound by name, so the `:name` parameters are rewritten to positional ones.
+    fn statement<'a>(
+        &'a self,
+        event: &'a Value,
+        placeholder: Placeholder,
+    ) -> Result<(Cow<'a, str>, Vec<&'a Value>), AncymonError> {
+        if self.bind_one {
+            return Ok((Cow::Borrowed(&self.query), vec![event]));
+        }
+        if !self.bind_many {
+            return Ok((Cow::Borrowed(&self.query), Vec::new()));
+        }
+        match event {
+            Value::Array(values) => Ok((Cow::Borrowed(&self.query), values.iter().collect())),
+            Value::Map(values) => {
+                let (query, names) = rewrite_named(&self.query, placeholder);
+                let binds = names
+                    .iter()
+                    .map(|name| {
+                        values
+                            .get(name)
+                            .ok_or(RuntimeError::InvalidArguments(format!(
+                                "Value for sql parameter `{name}` not found"
+                            )))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((Cow::Owned(query), binds))
+            }
+            _ => Err(RuntimeError::InvalidArgumentType(
+                "Sql `bind-many` requires an array or a map".to_string(),
+            )
+            .into()),
+        }
+    }
+}
 
-fn map_row(row: &AnyRow) -> Result<Value, AncymonError> {
-    if row.is_empty() {
-        return Ok(Value::Null);
+/// Positional parameter syntax of the database driver.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Placeholder {
+    /// `?` - SQLite, MySQL
+    Question,
+    /// `$1` - PostgreSQL
+    Dollar,
+}
+impl Placeholder {
+    fn from_connection_string(s: &str) -> Self {
+        if s.starts_with("postgres") {
+            Self::Dollar
+        } else {
+            Self::Question
+        }
     }
-    if row.len() == 1 {
-        return map_db_value(row, 0);
+}
+
+/// Replace `:name` parameters with positional ones.
+/// Returns the new query and the parameter names in bind order.
+fn rewrite_named(query: &str, placeholder: Placeholder) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(query.len());
+    let mut names: Vec<String> = Vec::new();
+    let mut chars = query.chars().peekable();
+    let mut quote = None;
+    let mut prev = None;
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+        } else if c == '\'' || c == '"' {
+            quote = Some(c);
+        } else if c == ':'
+            && prev != Some(':')
+            && chars
+                .peek()
+                .is_some_and(|a| a.is_ascii_alphabetic() || *a == '_')
+        {
+            let mut name = String::new();
+            while let Some(&a) = chars.peek() {
+                if !a.is_ascii_alphanumeric() && a != '_' {
+                    break;
+                }
+                name.push(a);
+                chars.next();
+            }
+            match placeholder {
+                Placeholder::Question => {
+                    output.push('?');
+                    names.push(name);
+                }
+                Placeholder::Dollar => {
+                    let idx = match names.iter().position(|a| *a == name) {
+                        Some(idx) => idx,
+                        None => {
+                            names.push(name);
+                            names.len() - 1
+                        }
+                    };
+                    output.push_str(&format!("${}", idx + 1));
+                }
+            }
+            prev = None;
+            continue;
+        }
+        output.push(c);
+        prev = Some(c);
     }
-    let v = (0..row.len())
-        .map(|i| map_db_value(row, i))
-        .collect::<Result<Vec<_>, AncymonError>>()?;
-    Ok(Value::Array(v))
+    (output, names)
+}
+
+fn bind_value<'q>(
+    query: Query<'q, Any, AnyArguments<'q>>,
+    value: &Value,
+) -> Result<Query<'q, Any, AnyArguments<'q>>, AncymonError> {
+    match value {
+        Value::Null => Ok(query.bind(None::<String>)),
+        Value::Bool(b) => Ok(query.bind(*b)),
+        Value::Integer(i) => Ok(query.bind(*i)),
+        Value::Float(f) => Ok(query.bind(*f)),
+        Value::String(s) => Ok(query.bind(s.to_string())),
+        Value::Bytes(b) => Ok(query.bind(b.to_vec())),
+        Value::DateTime(d) => Ok(query.bind(d.to_rfc3339())),
+        Value::Array(_) | Value::Map(_) => Err(RuntimeError::InvalidArgumentType(
+            "Arrays and maps can not be bound as sql parameters".to_string(),
+        )
+        .into()),
+    }
+}
+
+fn map_row(row: &AnyRow, arguments: &SqlArguments) -> Result<Value, AncymonError> {
+    match arguments.row_format {
+        RowFormat::Auto => {
+            if row.is_empty() {
+                return Ok(Value::Null);
+            }
+            if row.len() == 1 {
+                return map_column(row, 0, arguments);
+            }
+            let v = (0..row.len())
+                .map(|i| map_column(row, i, arguments))
+                .collect::<Result<Vec<_>, AncymonError>>()?;
+            Ok(Value::Array(v))
+        }
+        RowFormat::Array => {
+            let v = (0..row.len())
+                .map(|i| map_column(row, i, arguments))
+                .collect::<Result<Vec<_>, AncymonError>>()?;
+            Ok(Value::Array(v))
+        }
+        RowFormat::Map => {
+            let m = row
+                .columns()
+                .iter()
+                .map(|c| {
+                    Ok((
+                        c.name().to_string(),
+                        map_column(row, c.ordinal(), arguments)?,
+                    ))
+                })
+                .collect::<Result<HashMap<_, _>, AncymonError>>()?;
+            Ok(Value::Map(m))
+        }
+    }
+}
+
+/// `INSERT` or SQLite's `REPLACE` statement, the only ones with a last insert id.
+fn is_insert(sql: &str) -> bool {
+    let keyword = sql
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    keyword.eq_ignore_ascii_case("insert") || keyword.eq_ignore_ascii_case("replace")
+}
+
+fn map_query_result(result: &AnyQueryResult, last_insert_id: Option<i64>) -> Value {
+    Value::Map(HashMap::from_iter(vec![
+        (
+            "rows_affected".to_string(),
+            // Saturate instead of wrapping, on the off chance of more than i64::MAX rows.
+            Value::Integer(i64::try_from(result.rows_affected()).unwrap_or(i64::MAX)),
+        ),
+        (
+            "last_insert_id".to_string(),
+            last_insert_id.map(Value::Integer).unwrap_or_default(),
+        ),
+    ]))
+}
+
+/// Map a column value, decoding it if the column was listed as JSON or datetime.
+fn map_column(row: &AnyRow, idx: usize, arguments: &SqlArguments) -> Result<Value, AncymonError> {
+    let value = map_db_value(row, idx)?;
+    let name = row.column(idx).name();
+
+    if arguments.json_columns.iter().any(|a| a == name) {
+        return decode_json(name, value);
+    }
+    if arguments.datetime_columns.iter().any(|a| a == name) {
+        return decode_datetime(name, value);
+    }
+    Ok(value)
+}
+
+fn decode_json(name: &str, value: Value) -> Result<Value, AncymonError> {
+    let json = match &value {
+        Value::Null => return Ok(Value::Null),
+        Value::String(s) => serde_json::from_str::<serde_json::Value>(s),
+        Value::Bytes(b) => serde_json::from_slice::<serde_json::Value>(b),
+        _ => {
+            return Err(RuntimeError::InvalidArgumentType(format!(
+                "Column `{name}` can not be decoded as JSON"
+            ))
+            .into());
+        }
+    };
+    json.map(Value::from).map_err(|e| {
+        RuntimeError::Handler {
+            message: format!("Invalid JSON in column `{name}`"),
+            source: Some(Arc::new(e)),
+        }
+        .into()
+    })
+}
+
+/// Decode ISO 8601 text or unix timestamp columns.
+fn decode_datetime(name: &str, value: Value) -> Result<Value, AncymonError> {
+    let datetime = match &value {
+        Value::Null => return Ok(Value::Null),
+        Value::String(s) => parse_datetime(s),
+        Value::Integer(i) => DateTime::from_timestamp(*i, 0),
+        Value::Float(f) => DateTime::from_timestamp_millis((f * 1000.).round() as i64),
+        _ => None,
+    };
+    datetime.map(Value::DateTime).ok_or(
+        RuntimeError::InvalidArgumentType(format!(
+            "Column `{name}` can not be decoded as datetime"
+        ))
+        .into(),
+    )
 }
 
 macro_rules! map_nullable {
     ($variant:ident, $row:ident, $ty:ty, $idx:expr) => {
-        if let Some(value) = $row.try_get::<Option<$ty>, _>($idx).map_err(|e| {
-            RuntimeError::Handler(format!("Invalid type for column at index {}. {}", $idx, e))
-        })? {
+        if let Some(value) =
+            $row.try_get::<Option<$ty>, _>($idx)
+                .map_err(|e| RuntimeError::Handler {
+                    message: format!("Invalid type for column at index {}", $idx),
+                    source: Some(Arc::new(e)),
+                })?
+        {
             Value::$variant(value)
         } else {
             Value::Null
@@ -167,14 +663,13 @@ fn map_db_value(row: &AnyRow, idx: usize) -> Result<Value, AncymonError> {
     let kind = row
         .columns()
         .get(idx)
-        .ok_or(RuntimeError::Handler(format!(
-            "Column at index {idx} not found"
-        )))?
+        .ok_or(RuntimeError::Handler {
+            message: format!("Column at index {idx} not found"),
+            source: None,
+        })?
         .type_info()
         .kind();
 
-    println!("{kind:?}");
-
     match kind {
         AnyTypeInfoKind::Null => Ok(Value::Null),
         AnyTypeInfoKind::Bool => Ok(map_nullable!(Bool, row, bool, idx)),
@@ -183,14 +678,13 @@ fn map_db_value(row: &AnyRow, idx: usize) -> Result<Value, AncymonError> {
         }
         AnyTypeInfoKind::Real | AnyTypeInfoKind::Double => Ok(map_nullable!(Float, row, f64, idx)),
         AnyTypeInfoKind::Text => Ok(map_nullable!(String, row, String, idx)),
-        AnyTypeInfoKind::Blob => {
-            Err(RuntimeError::InvalidArgumentType("Blobs are not supported".to_string()).into())
-        }
+        AnyTypeInfoKind::Blob => Ok(map_nullable!(Bytes, row, Vec<u8>, idx)),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use sqlx::Connection;
     use std::{collections::HashMap, str::FromStr};
 
     use super::*;
@@ -205,11 +699,21 @@ mod tests {
 
         let conn = AnyConnection::connect(&connection_str)
             .await
-            .map_err(|e| RuntimeError::Handler(format!("Sql connection failed:{e}")))
+            .map_err(|e| RuntimeError::Handler {
+                message: "Sql connection failed".to_string(),
+                source: Some(Arc::new(e)),
+            })
             .unwrap();
         (conn, handler)
     }
 
+    fn statement(query: &str) -> Value {
+        Value::Map(HashMap::from_iter(vec![
+            ("query".to_string(), Value::String(query.to_string())),
+            ("execute".to_string(), Value::Bool(true)),
+        ]))
+    }
+
     #[tokio::test]
     async fn fetch_one() {
         let (mut conn, handler) = db("fetch_one").await;
@@ -403,4 +907,513 @@ mod tests {
             ])
         )
     }
+
+    #[tokio::test]
+    async fn bind_one() {
+        let (mut conn, handler) = db("bind_one").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO sensor(id, value) VALUES ('temp', 3), ('humidity', 40)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::String("humidity".to_string()),
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("SELECT value FROM sensor WHERE id = ?;".to_string()),
+                    ),
+                    ("bind-one".to_string(), Value::Bool(true)),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Integer(40))
+    }
+    #[tokio::test]
+    async fn bind_many() {
+        let (mut conn, handler) = db("bind_many").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value real );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO sensor(id, value) VALUES ('temp', 3.5), ('temp', 7.5)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Array(vec![Value::String("temp".to_string()), Value::Float(5.0)]),
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String(
+                            "SELECT value FROM sensor WHERE id = ? AND value > ?;".to_string(),
+                        ),
+                    ),
+                    ("bind-many".to_string(), Value::Bool(true)),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Float(7.5))
+    }
+    #[tokio::test]
+    async fn bind_named() {
+        let (mut conn, handler) = db("bind_named").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer, note text );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO sensor(id, value, note) VALUES ('temp', 3, ':id'), ('temp', 9, null)",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Map(HashMap::from_iter(vec![
+                    ("id".to_string(), Value::String("temp".to_string())),
+                    ("min".to_string(), Value::Integer(1)),
+                ])),
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String(
+                            "SELECT note, value FROM sensor WHERE id = :id AND value > :min AND note = ':id';"
+                                .to_string(),
+                        ),
+                    ),
+                    ("bind-many".to_string(), Value::Bool(true)),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Value::Array(vec![Value::String(":id".to_string()), Value::Integer(3)])
+        )
+    }
+    #[test]
+    fn rewrite_named_placeholders() {
+        let query = "SELECT a::text FROM t WHERE a = :a AND b = ':b' AND c = :c OR d = :a";
+        assert_eq!(
+            rewrite_named(query, Placeholder::Question),
+            (
+                "SELECT a::text FROM t WHERE a = ? AND b = ':b' AND c = ? OR d = ?".to_string(),
+                vec!["a".to_string(), "c".to_string(), "a".to_string()]
+            )
+        );
+        assert_eq!(
+            rewrite_named(query, Placeholder::Dollar),
+            (
+                "SELECT a::text FROM t WHERE a = $1 AND b = ':b' AND c = $2 OR d = $1".to_string(),
+                vec!["a".to_string(), "c".to_string()]
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn execute_insert() {
+        let (mut conn, handler) = db("execute_insert").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value real );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Array(vec![Value::String("temp".to_string()), Value::Float(21.5)]),
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("INSERT INTO sensor(id, value) VALUES (?, ?);".to_string()),
+                    ),
+                    ("bind-many".to_string(), Value::Bool(true)),
+                    ("execute".to_string(), Value::Bool(true)),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Value::Map(HashMap::from_iter(vec![
+                ("rows_affected".to_string(), Value::Integer(1)),
+                ("last_insert_id".to_string(), Value::Integer(1)),
+            ]))
+        );
+
+        let value: f64 = sqlx::query_scalar("SELECT value FROM sensor WHERE id = 'temp'")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(value, 21.5);
+    }
+    #[tokio::test]
+    async fn execute_update() {
+        let (mut conn, handler) = db("execute_update").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        handler
+            .execute(
+                &Value::Null,
+                &statement(
+                    "INSERT INTO sensor(id, value) VALUES ('temp', 3), ('temp', 4), ('hum', 5)",
+                ),
+            )
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("UPDATE sensor SET value = 0 WHERE id = 'temp';".to_string()),
+                    ),
+                    ("execute".to_string(), Value::Bool(true)),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.as_map().unwrap()["rows_affected"], Value::Integer(2));
+        assert_eq!(result.as_map().unwrap()["last_insert_id"], Value::Null);
+    }
+
+    #[test]
+    fn insert_statements() {
+        assert!(is_insert("INSERT INTO sensor VALUES (1)"));
+        assert!(is_insert("\n  insert into sensor VALUES (1)"));
+        assert!(is_insert("REPLACE INTO sensor VALUES (1)"));
+        assert!(!is_insert("UPDATE sensor SET value = 0"));
+        assert!(!is_insert("DELETE FROM sensor"));
+        assert!(!is_insert("INSERTED"));
+    }
+
+    #[tokio::test]
+    async fn pool_shares_memory_db() {
+        let config = toml::Table::from_str(
+            "connection-string = \"sqlite::memory:\"\npool-size = 1\nmin-connections = 1",
+        )
+        .unwrap();
+        let mut handler = SqlHandler::default();
+        handler.init(&config).await.unwrap();
+
+        let execute = |query: &str| {
+            Value::Map(HashMap::from_iter(vec![
+                ("query".to_string(), Value::String(query.to_string())),
+                ("execute".to_string(), Value::Bool(true)),
+            ]))
+        };
+        handler
+            .execute(
+                &Value::Null,
+                &execute("CREATE TABLE sensor ( id text, value integer );"),
+            )
+            .await
+            .unwrap();
+        handler
+            .execute(
+                &Value::Null,
+                &execute("INSERT INTO sensor(id, value) VALUES ('temp', 3);"),
+            )
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![(
+                    "query".to_string(),
+                    Value::String("SELECT value FROM sensor;".to_string()),
+                )])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Integer(3));
+    }
+    #[tokio::test]
+    async fn pool_options() {
+        let config = toml::Table::from_str(
+            "connection-string = \"sqlite::memory:\"\npool-size = 2\nidle-timeout = \"5m\"\nacquire-timeout = 2.5",
+        )
+        .unwrap();
+        let mut handler = SqlHandler::default();
+        handler.init(&config).await.unwrap();
+
+        let options = handler.pool.as_ref().unwrap().options();
+        assert_eq!(options.get_max_connections(), 2);
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(300)));
+        assert_eq!(options.get_acquire_timeout(), Duration::from_millis(2500));
+    }
+
+    #[tokio::test]
+    async fn lazy_connect() {
+        let config = toml::Table::from_str(
+            "connection-string = \"sqlite:///missing/ancymon.db\"\nacquire-timeout = 1",
+        )
+        .unwrap();
+        let mut handler = SqlHandler::default();
+        handler.init(&config).await.unwrap();
+
+        let result = handler
+            .execute(&Value::Null, &statement("DELETE FROM sensor;"))
+            .await;
+        let Err(AncymonError::RuntimeError(error @ RuntimeError::Handler { .. })) = result else {
+            panic!("expected a handler error");
+        };
+        // The database error is kept as the source.
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(source.downcast_ref::<sqlx::Error>().is_some());
+    }
+
+    #[tokio::test]
+    async fn row_format_map() {
+        let (mut conn, handler) = db("row_format_map").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO sensor(id, value) VALUES ('temp', 3), ('hum', 40)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("SELECT id, value AS v FROM sensor ORDER BY v;".to_string()),
+                    ),
+                    ("fetch-many".to_string(), Value::Bool(true)),
+                    ("row-format".to_string(), Value::String("map".to_string())),
+                ])),
+            )
+            .await
+            .unwrap();
+
+        let arr = result.as_array().unwrap();
+        assert_eq!(
+            arr[0],
+            Value::Map(HashMap::from_iter(vec![
+                ("id".to_string(), Value::String("temp".to_string())),
+                ("v".to_string(), Value::Integer(3)),
+            ]))
+        );
+        assert_eq!(arr[1].as_map().unwrap()["v"], Value::Integer(40));
+    }
+    #[tokio::test]
+    async fn row_format_array() {
+        let (mut conn, handler) = db("row_format_array").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO sensor(id, value) VALUES ('temp', 3)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("SELECT value FROM sensor;".to_string()),
+                    ),
+                    ("row-format".to_string(), Value::String("array".to_string())),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Array(vec![Value::Integer(3)]));
+    }
+
+    #[tokio::test]
+    async fn fetch_blob() {
+        let (mut conn, handler) = db("fetch_blob").await;
+        sqlx::query("CREATE TABLE image ( id text, data blob );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        handler
+            .execute(
+                &Value::Array(vec![
+                    Value::String("cam".to_string()),
+                    Value::Bytes(vec![0, 159, 146, 150]),
+                ]),
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("INSERT INTO image(id, data) VALUES (?, ?);".to_string()),
+                    ),
+                    ("bind-many".to_string(), Value::Bool(true)),
+                    ("execute".to_string(), Value::Bool(true)),
+                ])),
+            )
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![(
+                    "query".to_string(),
+                    Value::String("SELECT data FROM image;".to_string()),
+                )])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Bytes(vec![0, 159, 146, 150]));
+    }
+    #[tokio::test]
+    async fn decode_columns() {
+        let (mut conn, handler) = db("decode_columns").await;
+        sqlx::query("CREATE TABLE sensor ( ts text, created integer, payload text );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"INSERT INTO sensor(ts, created, payload)
+            VALUES ('2026-02-14 12:33:06', 1771072386, '{"temp": 21.5, "tags": ["attic"]}')"#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![
+                    (
+                        "query".to_string(),
+                        Value::String("SELECT ts, created, payload FROM sensor;".to_string()),
+                    ),
+                    ("row-format".to_string(), Value::String("map".to_string())),
+                    (
+                        "json-columns".to_string(),
+                        Value::Array(vec![Value::String("payload".to_string())]),
+                    ),
+                    (
+                        "datetime-columns".to_string(),
+                        Value::Array(vec![
+                            Value::String("ts".to_string()),
+                            Value::String("created".to_string()),
+                        ]),
+                    ),
+                ])),
+            )
+            .await
+            .unwrap();
+
+        let map = result.as_map().unwrap();
+        let expected = Value::DateTime(DateTime::from_timestamp(1771072386, 0).unwrap());
+        assert_eq!(map["ts"], expected);
+        assert_eq!(map["created"], expected);
+        assert_eq!(
+            map["payload"],
+            Value::Map(HashMap::from_iter(vec![
+                ("temp".to_string(), Value::Float(21.5)),
+                (
+                    "tags".to_string(),
+                    Value::Array(vec![Value::String("attic".to_string())])
+                ),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction_commit() {
+        let (mut conn, handler) = db("transaction_commit").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE rollup ( id text, total integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO sensor(id, value) VALUES ('temp', 3), ('temp', 4)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![(
+                    "statements".to_string(),
+                    Value::Array(vec![
+                        statement(
+                            "INSERT INTO rollup SELECT id, sum(value) FROM sensor GROUP BY id;",
+                        ),
+                        statement("DELETE FROM sensor;"),
+                        Value::Map(HashMap::from_iter(vec![(
+                            "query".to_string(),
+                            Value::String("SELECT total FROM rollup;".to_string()),
+                        )])),
+                    ]),
+                )])),
+            )
+            .await
+            .unwrap();
+
+        let arr = result.as_array().unwrap();
+        assert_eq!(arr.len(), 3);
+        assert_eq!(arr[1].as_map().unwrap()["rows_affected"], Value::Integer(2));
+        // The insert before it ran on the same transaction.
+        assert_eq!(
+            arr[0].as_map().unwrap()["last_insert_id"],
+            Value::Integer(1)
+        );
+        assert_eq!(arr[1].as_map().unwrap()["last_insert_id"], Value::Null);
+        assert_eq!(arr[2], Value::Integer(7));
+    }
+    #[tokio::test]
+    async fn transaction_rollback() {
+        let (mut conn, handler) = db("transaction_rollback").await;
+        sqlx::query("CREATE TABLE sensor ( id text, value integer );")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO sensor(id, value) VALUES ('temp', 3)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let result = handler
+            .execute(
+                &Value::Null,
+                &Value::Map(HashMap::from_iter(vec![(
+                    "statements".to_string(),
+                    Value::Array(vec![
+                        statement("DELETE FROM sensor;"),
+                        statement("INSERT INTO missing(id) VALUES ('temp');"),
+                    ]),
+                )])),
+            )
+            .await;
+        assert!(result.is_err());
+
+        let count: i64 = sqlx::query_scalar("SELECT count(*) FROM sensor")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
 }
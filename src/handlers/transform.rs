@@ -0,0 +1,514 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+use crate::{
+    errors::{AncymonError, ConfigError, RuntimeError},
+    events::EventValue,
+    expressions::{truthy, Expression, Scope},
+    handlers::{EventHandler, HandlerBuilder},
+    values::{parse_datetime, Value},
+};
+
+/// Reshapes the event value with a list of steps, applied in order:
+///
+/// ```toml
+/// arguments = { steps = [
+///     { index = 0 },
+///     { pick = ["temp", "sensor", "time"] },
+///     { rename = { temp = "temperature" } },
+///     { convert = { temperature = "float", time = "datetime" } },
+/// ] }
+/// ```
+///
+/// Available steps:
+/// - `pick`: keep only the listed map keys
+/// - `omit`: remove the listed map keys
+/// - `rename`: rename map keys, `{ old = "new" }`
+/// - `index`: take an array element, negative indexes count from the end
+/// - `map`: apply a list of steps to every array element
+/// - `convert`: convert the value, or the given map keys, to `integer`, `float`,
+///   `string`, `bool`, `datetime` or `json`
+/// - `fields`: build a map from expressions of the value, eg. `{ title = "value.name" }`
+#[derive(Default)]
+pub struct TransformHandler;
+#[async_trait]
+impl EventHandler for TransformHandler {
+    async fn execute(&self, event: &Value, arguments: &Value) -> EventValue {
+        let arguments: TransformArguments = arguments.clone().try_into()?;
+        apply(&arguments.steps, event.clone())
+    }
+}
+
+pub struct TransformBuilder;
+impl HandlerBuilder for TransformBuilder {
+    fn build(&self) -> Result<Box<dyn EventHandler + Send + Sync>, AncymonError> {
+        Ok(Box::new(TransformHandler))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Conversion {
+    Integer,
+    Float,
+    String,
+    Bool,
+    DateTime,
+    Json,
+}
+impl TryFrom<&Value> for Conversion {
+    type Error = AncymonError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value.as_str().ok_or(ConfigError::InvalidValueType(
+            "Expected string type name for `convert`".to_string(),
+        ))? {
+            "integer" => Ok(Self::Integer),
+            "float" => Ok(Self::Float),
+            "string" => Ok(Self::String),
+            "bool" => Ok(Self::Bool),
+            "datetime" => Ok(Self::DateTime),
+            "json" => Ok(Self::Json),
+            a => Err(ConfigError::InvalidValue(format!("Unknown conversion type: {a}")).into()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Step {
+    Pick(Vec<String>),
+    Omit(Vec<String>),
+    Rename(Vec<(String, String)>),
+    Index(i64),
+    Map(Vec<Step>),
+    Convert(Conversion),
+    ConvertFields(Vec<(String, Conversion)>),
+    Fields(Vec<(String, Expression)>),
+}
+impl TryFrom<&Value> for Step {
+    type Error = AncymonError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let map = value.as_map().ok_or(ConfigError::InvalidValueType(
+            "Expected map for transform step".to_string(),
+        ))?;
+        let mut entries = map.iter();
+        let (Some((name, value)), None) = (entries.next(), entries.next()) else {
+            return Err(ConfigError::InvalidValue(
+                "Transform step has to contain exactly one operation".to_string(),
+            )
+            .into());
+        };
+
+        let step = match name.as_str() {
+            "pick" => Self::Pick(string_list(value, name)?),
+            "omit" => Self::Omit(string_list(value, name)?),
+            "rename" => Self::Rename(
+                string_map(value, name)?
+                    .into_iter()
+                    .map(|(k, v)| Ok((k, string(v, name)?)))
+                    .collect::<Result<_, AncymonError>>()?,
+            ),
+            "index" => Self::Index(value.as_int().ok_or(ConfigError::InvalidValueType(
+                "Expected integer for `index`".to_string(),
+            ))?),
+            "map" => Self::Map(steps(value)?),
+            "convert" => match value {
+                Value::String(_) => Self::Convert(value.try_into()?),
+                _ => Self::ConvertFields(
+                    string_map(value, name)?
+                        .into_iter()
+                        .map(|(k, v)| Ok((k, v.try_into()?)))
+                        .collect::<Result<_, AncymonError>>()?,
+                ),
+            },
+            "fields" => Self::Fields(
+                string_map(value, name)?
+                    .into_iter()
+                    .map(|(k, v)| {
+                        let expression: Expression = string(v, name)?.parse()?;
+                        // Steps only see the value, the event name is not available here.
+                        if expression.uses_event() {
+                            return Err(ConfigError::InvalidValue(format!(
+                                "Transform field `{k}` can not read `event`, use a \
+                                `'{{{{ event.name }}}}'` literal rendered by the action template"
+                            ))
+                            .into());
+                        }
+                        Ok((k, expression))
+                    })
+                    .collect::<Result<_, AncymonError>>()?,
+            ),
+            a => {
+                return Err(
+                    ConfigError::InvalidValue(format!("Unknown transform step: {a}")).into(),
+                );
+            }
+        };
+        Ok(step)
+    }
+}
+
+struct TransformArguments {
+    steps: Vec<Step>,
+}
+impl TryFrom<Value> for TransformArguments {
+    type Error = AncymonError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let map = value
+            .as_map()
+            .ok_or(ConfigError::InvalidValueType("Expected map".to_string()))?;
+        let steps = steps(map.get("steps").ok_or(ConfigError::MissingValue(
+            "Field `steps` is required".to_string(),
+        ))?)?;
+        Ok(Self { steps })
+    }
+}
+
+fn steps(value: &Value) -> Result<Vec<Step>, AncymonError> {
+    value
+        .as_array()
+        .ok_or(ConfigError::InvalidValueType(
+            "Expected array of transform steps".to_string(),
+        ))?
+        .iter()
+        .map(Step::try_from)
+        .collect()
+}
+
+fn string(value: &Value, key: &str) -> Result<String, AncymonError> {
+    Ok(value
+        .as_str()
+        .ok_or(ConfigError::InvalidValueType(format!(
+            "Expected string values for `{key}`"
+        )))?
+        .to_string())
+}
+
+fn string_list(value: &Value, key: &str) -> Result<Vec<String>, AncymonError> {
+    value
+        .as_array()
+        .ok_or(ConfigError::InvalidValueType(format!(
+            "Expected array of strings for `{key}`"
+        )))?
+        .iter()
+        .map(|a| string(a, key))
+        .collect()
+}
+
+/// Map entries sorted by key, so the steps are applied in a stable order.
+fn string_map<'a>(value: &'a Value, key: &str) -> Result<Vec<(String, &'a Value)>, AncymonError> {
+    let mut entries = value
+        .as_map()
+        .ok_or(ConfigError::InvalidValueType(format!(
+            "Expected map for `{key}`"
+        )))?
+        .iter()
+        .map(|(k, v)| (k.clone(), v))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+fn apply(steps: &[Step], value: Value) -> EventValue {
+    steps
+        .iter()
+        .try_fold(value, |value, step| apply_step(step, value))
+}
+
+fn apply_step(step: &Step, value: Value) -> EventValue {
+    let expected = |kind: &str| {
+        RuntimeError::InvalidArgumentType(format!("Transform {step:?} requires {kind} value"))
+    };
+
+    match step {
+        Step::Pick(keys) => {
+            let Value::Map(mut map) = value else {
+                return Err(expected("a map").into());
+            };
+            Ok(Value::Map(
+                keys.iter().filter_map(|k| map.remove_entry(k)).collect(),
+            ))
+        }
+        Step::Omit(keys) => {
+            let Value::Map(mut map) = value else {
+                return Err(expected("a map").into());
+            };
+            for key in keys {
+                map.remove(key);
+            }
+            Ok(Value::Map(map))
+        }
+        Step::Rename(keys) => {
+            let Value::Map(mut map) = value else {
+                return Err(expected("a map").into());
+            };
+            // Take every renamed value out first, so swaps and chains keep all values.
+            let renamed = keys
+                .iter()
+                .filter_map(|(from, to)| Some((to.clone(), map.remove(from)?)))
+                .collect::<Vec<_>>();
+            map.extend(renamed);
+            Ok(Value::Map(map))
+        }
+        Step::Index(index) => {
+            let Value::Array(mut array) = value else {
+                return Err(expected("an array").into());
+            };
+            let index = if *index < 0 {
+                array.len().checked_sub(index.unsigned_abs() as usize)
+            } else {
+                Some(*index as usize)
+            };
+            Ok(index
+                .filter(|a| *a < array.len())
+                .map(|a| array.swap_remove(a))
+                .unwrap_or_default())
+        }
+        Step::Map(steps) => {
+            let Value::Array(array) = value else {
+                return Err(expected("an array").into());
+            };
+            Ok(Value::Array(
+                array
+                    .into_iter()
+                    .map(|a| apply(steps, a))
+                    .collect::<Result<_, _>>()?,
+            ))
+        }
+        Step::Convert(conversion) => convert(value, *conversion),
+        Step::ConvertFields(fields) => {
+            let Value::Map(mut map) = value else {
+                return Err(expected("a map").into());
+            };
+            for (key, conversion) in fields {
+                if let Some(v) = map.remove(key) {
+                    map.insert(key.clone(), convert(v, *conversion)?);
+                }
+            }
+            Ok(Value::Map(map))
+        }
+        Step::Fields(fields) => {
+            // Expressions reading the event are rejected when parsing the step.
+            let scope = Scope {
+                value: &value,
+                event: "",
+            };
+            Ok(Value::Map(HashMap::from_iter(
+                fields.iter().map(|(k, e)| (k.clone(), e.evaluate(&scope))),
+            )))
+        }
+    }
+}
+
+fn convert(value: Value, conversion: Conversion) -> EventValue {
+    let error = |value: &Value| {
+        RuntimeError::InvalidArgumentType(format!("Can not convert {value:?} to {conversion:?}"))
+    };
+
+    let converted = match (conversion, &value) {
+        (_, Value::Null) => Some(Value::Null),
+        (Conversion::Integer, Value::Integer(_)) => Some(value.clone()),
+        (Conversion::Integer, Value::Float(f)) => Some(Value::Integer(f.trunc() as i64)),
+        (Conversion::Integer, Value::Bool(b)) => Some(Value::Integer(*b as i64)),
+        (Conversion::Integer, Value::String(s)) => s.trim().parse().ok().map(Value::Integer),
+        (Conversion::Integer, Value::DateTime(d)) => Some(Value::Integer(d.timestamp())),
+        (Conversion::Float, Value::Float(_)) => Some(value.clone()),
+        (Conversion::Float, Value::Integer(i)) => Some(Value::Float(*i as f64)),
+        (Conversion::Float, Value::String(s)) => s.trim().parse().ok().map(Value::Float),
+        (Conversion::String, Value::String(_)) => Some(value.clone()),
+        (Conversion::String, Value::Bytes(b)) => {
+            String::from_utf8(b.clone()).ok().map(Value::String)
+        }
+        (Conversion::String, v) => Some(Value::String(v.pretty())),
+        (Conversion::Bool, Value::String(s)) => match s.trim() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        (Conversion::Bool, v) => Some(Value::Bool(truthy(v))),
+        (Conversion::DateTime, Value::DateTime(_)) => Some(value.clone()),
+        (Conversion::DateTime, Value::String(s)) => parse_datetime(s).map(Value::DateTime),
+        (Conversion::DateTime, Value::Integer(i)) => {
+            chrono::DateTime::from_timestamp(*i, 0).map(Value::DateTime)
+        }
+        (Conversion::Json, Value::String(s)) => serde_json::from_str::<serde_json::Value>(s)
+            .ok()
+            .map(Value::from),
+        _ => None,
+    };
+    converted.ok_or(error(&value).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(values: Vec<(&str, Value)>) -> Value {
+        Value::Map(HashMap::from_iter(
+            values.into_iter().map(|(k, v)| (k.to_string(), v)),
+        ))
+    }
+
+    async fn transform(event: Value, steps: &str) -> EventValue {
+        let arguments = toml::from_str::<Value>(&format!("steps = {steps}")).unwrap();
+        TransformHandler.execute(&event, &arguments).await
+    }
+
+    fn rows() -> Value {
+        Value::Array(vec![
+            map(vec![
+                ("temp", Value::String("23.5".to_string())),
+                ("sensor", Value::String("attic".to_string())),
+                ("time", Value::String("2026-02-14T12:33:06Z".to_string())),
+            ]),
+            map(vec![
+                ("temp", Value::String("19".to_string())),
+                ("sensor", Value::String("garage".to_string())),
+                ("time", Value::Null),
+            ]),
+        ])
+    }
+
+    #[tokio::test]
+    async fn pipeline() {
+        let result = transform(
+            rows(),
+            r#"[
+                { index = -2 },
+                { pick = ["temp", "time"] },
+                { rename = { temp = "temperature" } },
+                { convert = { temperature = "float", time = "datetime" } },
+            ]"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result,
+            map(vec![
+                ("temperature", Value::Float(23.5)),
+                (
+                    "time",
+                    Value::DateTime(chrono::DateTime::from_timestamp(1771072386, 0).unwrap())
+                ),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn map_fields() {
+        let result = transform(
+            rows(),
+            r#"[
+                { map = [
+                    { omit = ["time"] },
+                    { fields = { name = "value.sensor", hot = "value.temp > '20'" } },
+                ] },
+            ]"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            result,
+            Value::Array(vec![
+                map(vec![
+                    ("name", Value::String("attic".to_string())),
+                    ("hot", Value::Bool(true)),
+                ]),
+                map(vec![
+                    ("name", Value::String("garage".to_string())),
+                    ("hot", Value::Bool(false)),
+                ]),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_swap() {
+        let value = map(vec![
+            ("a", Value::Integer(1)),
+            ("b", Value::Integer(2)),
+            ("c", Value::Integer(3)),
+        ]);
+        assert_eq!(
+            transform(value.clone(), r#"[{ rename = { a = "b", b = "a" } }]"#)
+                .await
+                .unwrap(),
+            map(vec![
+                ("a", Value::Integer(2)),
+                ("b", Value::Integer(1)),
+                ("c", Value::Integer(3)),
+            ])
+        );
+        assert_eq!(
+            transform(value, r#"[{ rename = { a = "b", b = "c", c = "d" } }]"#)
+                .await
+                .unwrap(),
+            map(vec![
+                ("b", Value::Integer(1)),
+                ("c", Value::Integer(2)),
+                ("d", Value::Integer(3)),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn convert_values() {
+        let json = Value::String(r#"{"a": [1, 2.5, true]}"#.to_string());
+        assert_eq!(
+            transform(json, r#"[{ convert = "json" }]"#).await.unwrap(),
+            map(vec![(
+                "a",
+                Value::Array(vec![
+                    Value::Integer(1),
+                    Value::Float(2.5),
+                    Value::Bool(true)
+                ])
+            )])
+        );
+        assert_eq!(
+            transform(Value::Float(2.9), r#"[{ convert = "integer" }]"#)
+                .await
+                .unwrap(),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            transform(Value::Integer(0), r#"[{ convert = "bool" }]"#)
+                .await
+                .unwrap(),
+            Value::Bool(false)
+        );
+        assert!(transform(
+            Value::String("abc".to_string()),
+            r#"[{ convert = "float" }]"#
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn out_of_bounds() {
+        assert_eq!(
+            transform(rows(), "[{ index = 2 }]").await.unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            transform(rows(), "[{ index = -3 }]").await.unwrap(),
+            Value::Null
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        for steps in [
+            "[{ pick = [\"a\"] }]",
+            "[{ unknown = 1 }]",
+            "[{ pick = [\"a\"], omit = [\"b\"] }]",
+            "[{ convert = \"complex\" }]",
+            "[{ fields = { a = \"value >\" } }]",
+            "[{ fields = { a = \"event.name\" } }]",
+        ] {
+            assert!(transform(rows(), steps).await.is_err(), "{steps}");
+        }
+    }
+}
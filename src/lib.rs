@@ -2,9 +2,14 @@ mod actions;
 pub mod bot;
 mod config;
 pub mod errors;
-mod events;
+/// Public for sources registered with [`Bot::with_source_type`], which send events.
+pub mod events;
+mod expressions;
 pub mod handlers;
+mod limits;
+mod templates;
 pub mod triggers;
+pub mod validation;
 pub mod values;
 
 pub use bot::Bot;
@@ -0,0 +1,174 @@
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+
+const DEFAULT_QUEUE_SIZE: usize = 256;
+
+/// Concurrency limit settings, used both globally (`[limits]`) and per handler.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct Limits {
+    /// Zero is rejected, it would park every execution forever.
+    #[serde(rename = "max-concurrent")]
+    pub(crate) max_concurrent: Option<NonZeroUsize>,
+    #[serde(default)]
+    pub(crate) overflow: Overflow,
+    /// Max number of waiting executions for the `drop-oldest` policy.
+    #[serde(rename = "queue-size")]
+    pub(crate) queue_size: Option<usize>,
+}
+
+/// What to do when the concurrency limit is reached.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overflow {
+    /// Wait for a free slot.
+    #[default]
+    Queue,
+    /// Wait for a free slot, dropping the longest waiting execution when the queue is full.
+    DropOldest,
+    /// Fail immediately.
+    Reject,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Overflowed {
+    Rejected,
+    Dropped,
+}
+
+pub(crate) struct Limiter {
+    semaphore: Arc<Semaphore>,
+    overflow: Overflow,
+    queue_size: usize,
+    waiting: Mutex<VecDeque<(u64, oneshot::Sender<()>)>>,
+    next_id: AtomicU64,
+}
+impl Limiter {
+    /// Returns `None` when no limit is set.
+    pub(crate) fn new(limits: &Limits) -> Option<Self> {
+        let max = limits.max_concurrent?;
+        Some(Self {
+            semaphore: Arc::new(Semaphore::new(max.get())),
+            overflow: limits.overflow,
+            queue_size: limits.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE).max(1),
+            waiting: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+        })
+    }
+    pub(crate) fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+    /// Wait for an execution slot, according to the overflow policy.
+    /// The slot is released when the permit is dropped.
+    pub(crate) async fn acquire(&self) -> Result<OwnedSemaphorePermit, Overflowed> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        match self.overflow {
+            Overflow::Reject => Err(Overflowed::Rejected),
+            Overflow::Queue => self
+                .semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|_| Overflowed::Rejected),
+            Overflow::DropOldest => {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                let (tx, dropped) = oneshot::channel();
+                {
+                    let mut waiting = self.waiting.lock().unwrap();
+                    if waiting.len() >= self.queue_size
+                        && let Some((_, oldest)) = waiting.pop_front()
+                    {
+                        let _ = oldest.send(());
+                    }
+                    waiting.push_back((id, tx));
+                }
+
+                let result = tokio::select! {
+                    permit = self.semaphore.clone().acquire_owned() => {
+                        permit.map_err(|_| Overflowed::Dropped)
+                    }
+                    _ = dropped => Err(Overflowed::Dropped),
+                };
+                self.waiting.lock().unwrap().retain(|a| a.0 != id);
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn limiter(max: usize, overflow: Overflow, queue_size: usize) -> Arc<Limiter> {
+        Arc::new(
+            Limiter::new(&Limits {
+                max_concurrent: NonZeroUsize::new(max),
+                overflow,
+                queue_size: Some(queue_size),
+            })
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn no_limit() {
+        assert!(Limiter::new(&Limits::default()).is_none());
+    }
+
+    #[test]
+    fn zero_limit() {
+        assert!(toml::from_str::<Limits>("max-concurrent = 0").is_err());
+        assert!(toml::from_str::<Limits>("max-concurrent = 1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn reject() {
+        let limiter = limiter(1, Overflow::Reject, 1);
+        let permit = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.acquire().await.err(), Some(Overflowed::Rejected));
+        drop(permit);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn queue() {
+        let limiter = limiter(1, Overflow::Queue, 1);
+        let permit = limiter.acquire().await.unwrap();
+
+        let waiting = limiter.clone();
+        let handle = tokio::spawn(async move { waiting.acquire().await.is_ok() });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!handle.is_finished());
+
+        drop(permit);
+        assert!(handle.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let limiter = limiter(1, Overflow::DropOldest, 1);
+        let permit = limiter.acquire().await.unwrap();
+
+        let waiting = limiter.clone();
+        let oldest = tokio::spawn(async move { waiting.acquire().await.err() });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let waiting = limiter.clone();
+        let newest = tokio::spawn(async move { waiting.acquire().await.err() });
+
+        assert_eq!(oldest.await.unwrap(), Some(Overflowed::Dropped));
+        drop(permit);
+        assert_eq!(newest.await.unwrap(), None);
+    }
+}
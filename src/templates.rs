@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use crate::{
+    errors::{AncymonError, ConfigError},
+    expressions::{Expression, Scope},
+    values::Value,
+};
+
+/// Action arguments with `{{ expression }}` placeholders in strings,
+/// eg. `"Temperature is {{ value.temp }}°C at {{ event.name }}"`.
+///
+/// A string consisting of a single placeholder keeps the type of the result,
+/// so `"{{ value.temp }}"` renders to a float instead of a string.
+/// Strings without placeholders are passed unchanged, and `\{{` writes a literal `{{`.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(try_from = "Value")]
+pub(crate) struct Template {
+    source: Value,
+    node: Option<Node>,
+}
+impl Template {
+    pub(crate) fn render(&self, scope: &Scope) -> Value {
+        match &self.node {
+            Some(node) => node.render(scope),
+            None => self.source.clone(),
+        }
+    }
+    /// Whether a value under `key`, at any depth, contains placeholders.
+    pub(crate) fn renders_key(&self, key: &str) -> bool {
+        self.node.as_ref().is_some_and(|a| a.renders_key(key))
+    }
+}
+impl TryFrom<Value> for Template {
+    type Error = AncymonError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node: Node::compile(&value)?,
+            source: value,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Text(String),
+    Expression(Expression),
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Literal(Value),
+    Single(Expression),
+    Text(Vec<Part>),
+    Array(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+impl Node {
+    /// `None` when the value has no placeholders.
+    fn compile(value: &Value) -> Result<Option<Self>, AncymonError> {
+        match value {
+            Value::String(s) => {
+                let mut parts = parse(s)?;
+                if parts.len() > 1 {
+                    return Ok(Some(Self::Text(parts)));
+                }
+                match parts.pop() {
+                    Some(Part::Expression(e)) => Ok(Some(Self::Single(e))),
+                    // Escaped placeholders only.
+                    Some(Part::Text(t)) if t != *s => Ok(Some(Self::Literal(Value::String(t)))),
+                    _ => Ok(None),
+                }
+            }
+            Value::Array(a) => {
+                let nodes = a.iter().map(Self::compile).collect::<Result<Vec<_>, _>>()?;
+                if nodes.iter().all(Option::is_none) {
+                    return Ok(None);
+                }
+                Ok(Some(Self::Array(
+                    nodes
+                        .into_iter()
+                        .zip(a)
+                        .map(|(node, v)| node.unwrap_or(Self::Literal(v.clone())))
+                        .collect(),
+                )))
+            }
+            Value::Map(m) => {
+                let nodes = m
+                    .iter()
+                    .map(|(k, v)| Ok((k, v, Self::compile(v)?)))
+                    .collect::<Result<Vec<_>, AncymonError>>()?;
+                if nodes.iter().all(|a| a.2.is_none()) {
+                    return Ok(None);
+                }
+                Ok(Some(Self::Map(
+                    nodes
+                        .into_iter()
+                        .map(|(k, v, node)| (k.clone(), node.unwrap_or(Self::Literal(v.clone()))))
+                        .collect(),
+                )))
+            }
+            _ => Ok(None),
+        }
+    }
+    fn renders_key(&self, key: &str) -> bool {
+        match self {
+            Self::Array(a) => a.iter().any(|a| a.renders_key(key)),
+            Self::Map(m) => m.iter().any(|(k, node)| {
+                (k == key && !matches!(node, Self::Literal(_))) || node.renders_key(key)
+            }),
+            _ => false,
+        }
+    }
+    fn render(&self, scope: &Scope) -> Value {
+        match self {
+            Self::Literal(v) => v.clone(),
+            Self::Single(e) => e.evaluate(scope),
+            Self::Text(parts) => Value::String(
+                parts
+                    .iter()
+                    .map(|a| match a {
+                        Part::Text(s) => s.clone(),
+                        Part::Expression(e) => text(&e.evaluate(scope)),
+                    })
+                    .collect(),
+            ),
+            Self::Array(a) => Value::Array(a.iter().map(|a| a.render(scope)).collect()),
+            Self::Map(m) => Value::Map(HashMap::from_iter(
+                m.iter().map(|(k, v)| (k.clone(), v.render(scope))),
+            )),
+        }
+    }
+}
+
+fn parse(s: &str) -> Result<Vec<Part>, AncymonError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        if let Some(before) = rest[..start].strip_suffix('\\') {
+            text.push_str(before);
+            text.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let end = rest[start..]
+            .find("}}")
+            .ok_or(ConfigError::InvalidValue(format!(
+                "Unclosed template placeholder in `{s}`"
+            )))?;
+        text.push_str(&rest[..start]);
+        if !text.is_empty() {
+            parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+        let expression = rest[start + 2..start + end].trim().parse()?;
+        parts.push(Part::Expression(expression));
+        rest = &rest[start + end + 2..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.pretty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: Value, value: Value) -> Value {
+        let template = Template::try_from(template).unwrap();
+        template.render(&Scope {
+            value: &value,
+            event: "reading",
+        })
+    }
+
+    fn reading() -> Value {
+        Value::Map(HashMap::from_iter(vec![
+            ("temp".to_string(), Value::Float(23.5)),
+            ("sensor".to_string(), Value::String("attic".to_string())),
+        ]))
+    }
+
+    #[test]
+    fn interpolate() {
+        let template = Value::String(
+            "Temperature is {{ value.temp }}°C at {{event.name}} ({{ value.missing }})".to_string(),
+        );
+        assert_eq!(
+            render(template, reading()),
+            Value::String("Temperature is 23.5°C at reading ()".to_string())
+        );
+    }
+
+    #[test]
+    fn keep_type() {
+        let template = Value::String("{{ value.temp }}".to_string());
+        assert_eq!(render(template, reading()), Value::Float(23.5));
+
+        let template = Value::String(" {{ value.temp }}".to_string());
+        assert_eq!(
+            render(template, reading()),
+            Value::String(" 23.5".to_string())
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let template = Value::Map(HashMap::from_iter(vec![
+            ("query".to_string(), Value::String("SELECT 1".to_string())),
+            (
+                "bind-many".to_string(),
+                Value::Array(vec![
+                    Value::String("{{ value.sensor }}".to_string()),
+                    Value::Integer(1),
+                ]),
+            ),
+        ]));
+        let rendered = render(template, reading());
+        let map = rendered.as_map().unwrap();
+        assert_eq!(map["query"], Value::String("SELECT 1".to_string()));
+        assert_eq!(
+            map["bind-many"],
+            Value::Array(vec![Value::String("attic".to_string()), Value::Integer(1)])
+        );
+    }
+
+    #[test]
+    fn renders_key() {
+        let template = |s: &str| Template::try_from(toml::from_str::<Value>(s).unwrap()).unwrap();
+        assert!(template("query = '{{ value }}'").renders_key("query"));
+        assert!(template("statements = [{ query = 'a {{ value }}' }]").renders_key("query"));
+        assert!(!template("query = 'a'\nchannel = '{{ value }}'").renders_key("query"));
+        assert!(!template("query = 'a'").renders_key("query"));
+    }
+
+    #[test]
+    fn no_placeholders() {
+        let template = Template::try_from(Value::String("plain {text}".to_string())).unwrap();
+        assert!(template.node.is_none());
+    }
+
+    #[test]
+    fn escape() {
+        let template = Value::String(r"\{{ literal }} and {{ value.sensor }}".to_string());
+        assert_eq!(
+            render(template, reading()),
+            Value::String("{{ literal }} and attic".to_string())
+        );
+
+        let template = Value::String(r"\{{ value.temp }}".to_string());
+        assert_eq!(
+            render(template, reading()),
+            Value::String("{{ value.temp }}".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for s in ["{{ value.temp", "{{ temp }}", "{{ }}"] {
+            assert!(
+                Template::try_from(Value::String(s.to_string())).is_err(),
+                "{s}"
+            );
+        }
+    }
+}
@@ -1,36 +1,172 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use std::str::FromStr;
+use chrono::{offset::LocalResult, DateTime, NaiveDateTime, TimeDelta, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
 use crate::{
-    errors::{AncymonError, BuildError, ConfigError},
+    config::deserialize_duration,
+    errors::{report, AncymonError, BuildError, ConfigError},
     events::Event,
-    triggers::{Trigger, TriggerSource},
+    triggers::{errors, Trigger, TriggerSource},
     values::Value,
 };
 
+const DEFAULT_MAX_LOOKBACK: Duration = Duration::from_secs(24 * 3600);
+/// Longest uninterrupted sleep, so wall clock jumps are noticed in time.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+/// Ticks fired later than this were missed, and are not fired by [`CatchUp::Skip`].
+/// As long as the longest sleep, so a busy runtime or a full event queue only delays them.
+const MISSED_AFTER: TimeDelta = TimeDelta::seconds(MAX_SLEEP.as_secs() as i64);
+
+#[derive(Debug, Default, Deserialize)]
+struct CronConfig {
+    /// Default timezone for all the triggers of the source.
+    timezone: Option<String>,
+    /// JSON file keeping the last fired times, required by the `catch-up` policies
+    /// other than `skip`.
+    #[serde(rename = "state-path")]
+    state_path: Option<PathBuf>,
+}
+
+/// What to do with the ticks missed while the bot was down or stalled.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CatchUp {
+    /// Only fire the ticks on time, nothing missed before the start or during a stall.
+    #[default]
+    Skip,
+    /// Fire the latest missed tick once.
+    FireOnce,
+    /// Fire every missed tick, in order.
+    FireAll,
+}
+
+/// Trigger arguments, either a cron expression or a table
+/// with `schedule` and an optional IANA `timezone`, eg. `"Europe/Warsaw"`.
+#[derive(Debug, Deserialize)]
+struct CronArguments {
+    schedule: String,
+    timezone: Option<String>,
+    #[serde(rename = "catch-up", default)]
+    catch_up: CatchUp,
+    /// Ignore missed ticks older than this, 1 day by default.
+    #[serde(
+        rename = "max-lookback",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    max_lookback: Option<Duration>,
+}
+
+struct Schedule {
+    schedule: cron::Schedule,
+    tz: Tz,
+    catch_up: CatchUp,
+    max_lookback: TimeDelta,
+    /// Identifies the trigger in the persisted state.
+    key: String,
+}
+impl Schedule {
+    /// Ticks after the given time, in order.
+    /// Schedules are evaluated on the local wall clock, so DST changes shift the UTC time.
+    /// A local time repeated by a DST change fires once, a skipped one fires at the end
+    /// of the gap.
+    fn ticks(&self, after: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let local = after.with_timezone(&self.tz).naive_local().and_utc();
+        let mut previous = after;
+        self.schedule
+            .after(&local)
+            .map_while(|a| self.resolve(a.naive_utc()))
+            .filter(move |a| {
+                // Ticks within a gap, or already fired in the first pass of a repeated hour.
+                let next = *a > previous;
+                previous = previous.max(*a);
+                next
+            })
+    }
+    /// Instant of the given local time. Repeated times resolve to their first occurrence,
+    /// skipped ones to the first valid time after the gap.
+    fn resolve(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let mut local = local;
+        // Gaps are whole minutes, and never longer than a day.
+        for _ in 0..=24 * 60 {
+            match self.tz.from_local_datetime(&local) {
+                LocalResult::Single(a) | LocalResult::Ambiguous(a, _) => return Some(a.to_utc()),
+                LocalResult::None => local = local.with_second(0)? + TimeDelta::minutes(1),
+            }
+        }
+        None
+    }
+    /// First tick after the given time.
+    fn upcoming(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.ticks(after).next()
+    }
+    /// Ticks to fire now, given the last fired tick.
+    fn due(&self, last: DateTime<Utc>, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let start = match self.catch_up {
+            CatchUp::Skip => last,
+            CatchUp::FireOnce | CatchUp::FireAll => last.max(now - self.max_lookback),
+        };
+        let ticks = self.ticks(start).take_while(|a| *a <= now);
+        match self.catch_up {
+            CatchUp::Skip => match ticks.last() {
+                Some(tick) if now - tick > MISSED_AFTER => {
+                    tracing::warn!(
+                        "Cron tick {tick} of `{}` was missed and is skipped",
+                        self.key
+                    );
+                    Vec::new()
+                }
+                tick => tick.into_iter().collect(),
+            },
+            CatchUp::FireOnce => ticks.last().into_iter().collect(),
+            CatchUp::FireAll => ticks.collect(),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct CronTrigger {
-    schedules: Vec<cron::Schedule>,
+    schedules: Vec<Schedule>,
     triggers: Vec<Trigger>,
+    state_path: Option<PathBuf>,
+    /// Last fired times by schedule key, as unix timestamps.
+    state: HashMap<String, i64>,
 }
 impl CronTrigger {
-    /// Return next scheduled time + trigger indices to fire
-    fn next(&self) -> (DateTime<Utc>, Vec<usize>) {
-        let mut upcoming = self
-            .schedules
+    /// Last fired tick of every schedule when starting.
+    /// Without a persisted time there is nothing to catch up on.
+    fn initial_last(&self, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        self.schedules
             .iter()
-            .map(|a| a.upcoming(Utc).take(1).next().unwrap())
-            .enumerate()
-            .collect::<Vec<_>>();
-        upcoming.sort_by_key(|a| a.1);
-        let indices = upcoming
-            .iter()
-            .filter(|a| a.1 == upcoming[0].1)
-            .map(|a| a.0)
-            .collect();
-
-        (upcoming[0].1, indices)
+            .map(|a| match a.catch_up {
+                CatchUp::Skip => now,
+                CatchUp::FireOnce | CatchUp::FireAll => self
+                    .state
+                    .get(&a.key)
+                    .and_then(|a| DateTime::from_timestamp(*a, 0))
+                    .map(|a| a.min(now))
+                    .unwrap_or(now),
+            })
+            .collect()
+    }
+    /// Ticks are saved once their events are queued, not handled,
+    /// so a crash in between loses them.
+    async fn save_state(&mut self, last: &[DateTime<Utc>]) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        for (schedule, last) in self.schedules.iter().zip(last) {
+            self.state.insert(schedule.key.clone(), last.timestamp());
+        }
+        let result = match serde_json::to_string_pretty(&self.state) {
+            Ok(a) => tokio::fs::write(path, a).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Cron state could not be saved to {}: {e}", path.display());
+        }
     }
 }
 
@@ -44,42 +180,419 @@ impl TriggerSource for CronTrigger {
         if triggers.is_empty() {
             return Err(ConfigError::MissingValue("No cron triggers specified".to_string()).into());
         }
+        let (config, default_tz) = parse_config(config)?;
 
-        // Make sure schedules and triggers are synced.
-        self.schedules.clear();
-
-        for trigger in triggers.iter() {
-            let pat = trigger
-                .arguments
-                .as_str()
-                .ok_or(ConfigError::InvalidValueType(
-                    "Cron arguments: expected string".to_string(),
-                ))?;
-            let schedule = cron::Schedule::from_str(pat).unwrap();
-            self.schedules.push(schedule);
-        }
+        self.state = match &config.state_path {
+            Some(path) if path.exists() => {
+                let state = std::fs::read_to_string(path).map_err(|e| BuildError::Source {
+                    message: format!("Cron state {} could not be read", path.display()),
+                    source: Some(Arc::new(e)),
+                })?;
+                serde_json::from_str(&state).map_err(|e| {
+                    ConfigError::InvalidValue(format!(
+                        "Cron state {} is invalid: {e}",
+                        path.display()
+                    ))
+                })?
+            }
+            _ => HashMap::new(),
+        };
+        self.state_path = config.state_path;
 
+        // Make sure schedules and triggers are synced.
+        self.schedules = triggers
+            .iter()
+            .map(|a| parse_schedule(a, default_tz, self.state_path.is_some()))
+            .collect::<Result<Vec<_>, _>>()?;
         self.triggers = triggers;
 
         Ok(())
     }
+    fn check(config: &toml::Table, triggers: &[Trigger]) -> Vec<String> {
+        let (config, default_tz) = match parse_config(config) {
+            Ok(a) => a,
+            Err(e) => return vec![report(&e)],
+        };
+        errors(
+            triggers
+                .iter()
+                .map(|a| parse_schedule(a, default_tz, config.state_path.is_some())),
+        )
+    }
     async fn run(&mut self, tx: tokio::sync::mpsc::Sender<Event>) {
+        let mut last = self.initial_last(Utc::now());
         loop {
-            let (deadline, indices) = self.next();
-
-            // TODO check precision
-            let duration = deadline - Utc::now();
-            // FIXME might panic if duration equals 0
-            tokio::time::sleep(duration.to_std().unwrap()).await;
-            let value = Value::Integer(deadline.timestamp());
-            for i in indices {
-                tx.send(Event::new(
-                    self.triggers[i].emit.to_string(),
-                    Ok(value.clone()),
-                ))
+            let now = Utc::now();
+            let mut fired = false;
+            for (i, schedule) in self.schedules.iter().enumerate() {
+                let due = schedule.due(last[i], now);
+                for time in due.iter() {
+                    let event = Event::new(
+                        self.triggers[i].emit.to_string(),
+                        Ok(Value::Integer(time.timestamp())),
+                    );
+                    if tx.send(event).await.is_err() {
+                        tracing::debug!("Event queue closed, cron trigger stopped");
+                        return;
+                    }
+                }
+                if let Some(time) = due.last() {
+                    last[i] = *time;
+                    fired = true;
+                }
+                if schedule.catch_up == CatchUp::Skip {
+                    // Missed ticks are passed over.
+                    last[i] = now;
+                }
+            }
+            if fired {
+                self.save_state(&last).await;
+            }
+
+            let next = self
+                .schedules
+                .iter()
+                .zip(last.iter())
+                .filter_map(|(schedule, last)| schedule.upcoming(*last))
+                .min();
+            let Some(next) = next else {
+                tracing::info!("No upcoming cron ticks left");
+                return;
+            };
+            // Deadlines already passed are fired right away. When the clock moved back,
+            // the next tick is further away than expected, and is waited for in steps.
+            if let Ok(duration) = (next - Utc::now()).to_std() {
+                tokio::time::sleep(duration.min(MAX_SLEEP)).await;
+            }
+        }
+    }
+}
+
+/// Source config and its default timezone.
+fn parse_config(config: &toml::Table) -> Result<(CronConfig, Tz), AncymonError> {
+    let config: CronConfig = config.clone().try_into().map_err(|e| BuildError::Source {
+        message: "Invalid cron source config".to_string(),
+        source: Some(Arc::new(e)),
+    })?;
+    let default_tz = config
+        .timezone
+        .as_deref()
+        .map(parse_timezone)
+        .transpose()?
+        .unwrap_or(Tz::UTC);
+    Ok((config, default_tz))
+}
+
+fn parse_schedule(
+    trigger: &Trigger,
+    default_tz: Tz,
+    has_state: bool,
+) -> Result<Schedule, AncymonError> {
+    let invalid = |e: String| {
+        ConfigError::InvalidValue(format!(
+            "Cron arguments for trigger `{}`: {e}",
+            trigger.emit
+        ))
+    };
+    let arguments = match &trigger.arguments {
+        toml::Value::String(s) => CronArguments {
+            schedule: s.to_string(),
+            timezone: None,
+            catch_up: CatchUp::default(),
+            max_lookback: None,
+        },
+        toml::Value::Table(t) => t
+            .clone()
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(e.to_string()))?,
+        _ => {
+            return Err(ConfigError::InvalidValueType(format!(
+                "Cron arguments for trigger `{}`: expected string or table",
+                trigger.emit
+            ))
+            .into());
+        }
+    };
+    let tz = match &arguments.timezone {
+        Some(tz) => parse_timezone(tz)?,
+        None => default_tz,
+    };
+    let max_lookback = TimeDelta::from_std(arguments.max_lookback.unwrap_or(DEFAULT_MAX_LOOKBACK))
+        .map_err(|_| invalid("max-lookback is too long".to_string()))?;
+    if arguments.catch_up != CatchUp::Skip && !has_state {
+        return Err(ConfigError::MissingValue(format!(
+            "Cron arguments for trigger `{}`: catch-up other than `skip` requires \
+            `state-path` in the source config",
+            trigger.emit
+        ))
+        .into());
+    }
+    let schedule = cron::Schedule::from_str(&arguments.schedule).map_err(|e| {
+        invalid(format!(
+            "invalid cron expression `{}`: {e}",
+            arguments.schedule
+        ))
+    })?;
+    Ok(Schedule {
+        schedule,
+        tz,
+        catch_up: arguments.catch_up,
+        max_lookback,
+        key: format!("{} {}", trigger.emit, arguments.schedule),
+    })
+}
+
+fn parse_timezone(name: &str) -> Result<Tz, AncymonError> {
+    Tz::from_str(name)
+        .map_err(|_| ConfigError::InvalidValue(format!("Unknown timezone: {name}")).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn trigger(config: &str, arguments: &[&str]) -> Result<CronTrigger, AncymonError> {
+        let triggers = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Trigger {
+                source: "cron".to_string(),
+                emit: format!("cron-{i}"),
+                arguments: toml::Value::from_str(a).unwrap(),
+            })
+            .collect();
+        let mut trigger = CronTrigger::default();
+        trigger
+            .init(&toml::Table::from_str(config).unwrap(), triggers)
+            .await?;
+        Ok(trigger)
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    fn state_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("ancymon-cron-{name}-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn timezone_dst() {
+        // Weekdays at 08:00 in Warsaw, across the switch to summer time on 2026-03-29.
+        let trigger = trigger("timezone = \"Europe/Warsaw\"", &["\"0 0 8 * * Mon-Fri *\""])
+            .await
+            .unwrap();
+        let schedule = &trigger.schedules[0];
+
+        let next = schedule.upcoming(utc("2026-03-27T06:00:00Z")).unwrap();
+        assert_eq!(next, utc("2026-03-27T07:00:00Z"));
+        let next = schedule.upcoming(next).unwrap();
+        assert_eq!(next, utc("2026-03-30T06:00:00Z"));
+    }
+
+    #[tokio::test]
+    async fn dst_transitions() {
+        let path = state_path("dst");
+        let config = format!(
+            "timezone = \"Europe/Warsaw\"\nstate-path = {:?}",
+            path.to_str().unwrap()
+        );
+        let trigger = trigger(
+            &config,
+            &[
+                "\"0 30 2 * * *\"",
+                "{ schedule = \"0 30 2 * * *\", catch-up = \"fire-all\" }",
+            ],
+        )
+        .await
+        .unwrap();
+        let schedule = &trigger.schedules[0];
+
+        // 02:30 is skipped on 2026-03-29, the tick fires when the clock jumps to 03:00.
+        let next = schedule.upcoming(utc("2026-03-28T12:00:00Z")).unwrap();
+        assert_eq!(next, utc("2026-03-29T01:00:00Z"));
+        let next = schedule.upcoming(next).unwrap();
+        assert_eq!(next, utc("2026-03-30T00:30:00Z"));
+
+        // 02:30 happens twice on 2026-10-25, only the first one fires.
+        let next = schedule.upcoming(utc("2026-10-24T12:00:00Z")).unwrap();
+        assert_eq!(next, utc("2026-10-25T00:30:00Z"));
+        let next = schedule.upcoming(next).unwrap();
+        assert_eq!(next, utc("2026-10-26T01:30:00Z"));
+        assert_eq!(
+            schedule.upcoming(utc("2026-10-25T01:00:00Z")),
+            Some(utc("2026-10-26T01:30:00Z"))
+        );
+
+        // Missed ticks are resolved the same way.
+        let fire_all = &trigger.schedules[1];
+        assert_eq!(
+            fire_all.due(utc("2026-03-28T12:00:00Z"), utc("2026-03-29T12:00:00Z")),
+            vec![utc("2026-03-29T01:00:00Z")]
+        );
+        assert_eq!(
+            fire_all.due(utc("2026-10-24T12:00:00Z"), utc("2026-10-25T12:00:00Z")),
+            vec![utc("2026-10-25T00:30:00Z")]
+        );
+    }
+
+    #[tokio::test]
+    async fn timezone_per_trigger() {
+        let trigger = trigger(
+            "timezone = \"Asia/Tokyo\"",
+            &[
+                "\"0 0 9 * * * *\"",
+                "{ schedule = \"0 0 9 * * * *\", timezone = \"UTC\" }",
+            ],
+        )
+        .await
+        .unwrap();
+
+        let after = utc("2026-01-01T01:00:00Z");
+        assert_eq!(
+            trigger.schedules[0].upcoming(after),
+            Some(utc("2026-01-02T00:00:00Z"))
+        );
+        assert_eq!(
+            trigger.schedules[1].upcoming(after),
+            Some(utc("2026-01-01T09:00:00Z"))
+        );
+    }
+
+    #[tokio::test]
+    async fn default_utc() {
+        let trigger = trigger("", &["\"0 30 12 * * * *\""]).await.unwrap();
+        assert_eq!(
+            trigger.schedules[0].upcoming(utc("2026-06-01T00:00:00Z")),
+            Some(utc("2026-06-01T12:30:00Z"))
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_timezone() {
+        assert!(
+            trigger("timezone = \"Mars/Olympus\"", &["\"* * * * * * *\""])
                 .await
+                .is_err()
+        );
+        assert!(trigger(
+            "",
+            &["{ schedule = \"* * * * * * *\", timezone = \"CEST+2\" }"]
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn catch_up_policies() {
+        let path = state_path("policies");
+        let trigger = trigger(
+            &format!("state-path = {:?}", path.to_str().unwrap()),
+            &[
+                "\"0 0 0 * * * *\"",
+                "{ schedule = \"0 0 0 * * * *\", catch-up = \"fire-once\" }",
+                "{ schedule = \"0 0 0 * * * *\", catch-up = \"fire-all\", max-lookback = \"3d\" }",
+            ],
+        )
+        .await
+        .unwrap();
+
+        let last = utc("2026-01-01T00:00:00Z");
+        let now = utc("2026-01-10T12:00:00Z");
+        // Nothing missed is fired by `skip`, only the tick on time.
+        assert!(trigger.schedules[0].due(last, now).is_empty());
+        assert_eq!(
+            trigger.schedules[0].due(last, utc("2026-01-10T00:00:30Z")),
+            vec![utc("2026-01-10T00:00:00Z")]
+        );
+        assert!(trigger.schedules[0]
+            .due(last, utc("2026-01-10T00:02:00Z"))
+            .is_empty());
+        assert_eq!(
+            trigger.schedules[1].due(last, now),
+            vec![utc("2026-01-10T00:00:00Z")]
+        );
+        assert_eq!(
+            trigger.schedules[2].due(last, now),
+            vec![
+                utc("2026-01-08T00:00:00Z"),
+                utc("2026-01-09T00:00:00Z"),
+                utc("2026-01-10T00:00:00Z"),
+            ]
+        );
+        assert!(trigger.schedules[2]
+            .due(utc("2026-01-10T00:00:00Z"), now)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn catch_up_requires_state() {
+        let result = trigger(
+            "",
+            &["{ schedule = \"0 0 0 * * * *\", catch-up = \"fire-once\" }"],
+        )
+        .await;
+        let Err(AncymonError::ConfigError(ConfigError::MissingValue(message))) = result else {
+            panic!("expected missing value");
+        };
+        assert!(message.contains("state-path"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn catch_up_after_restart() {
+        let path = state_path("restart");
+        let config = format!("state-path = {:?}", path.to_str().unwrap());
+        let arguments =
+            ["{ schedule = \"0 0 0 * * * *\", catch-up = \"fire-all\", max-lookback = \"10d\" }"];
+
+        // Nothing persisted yet, so nothing is missed.
+        let mut first = trigger(&config, &arguments).await.unwrap();
+        let now = Utc::now();
+        let last = first.initial_last(now);
+        assert_eq!(last, vec![now]);
+
+        let three_days_ago = now - TimeDelta::days(3);
+        first.save_state(&[three_days_ago]).await;
+
+        let mut second = trigger(&config, &arguments).await.unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move { second.run(tx).await });
+        for _ in 0..3 {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
                 .unwrap();
-            }
+            assert_eq!(event.name, "cron-0");
         }
+
+        // The fired ticks are persisted.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let state: HashMap<String, i64> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(state["cron-0 0 0 0 * * * *"] > three_days_ago.timestamp());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn invalid_expression() {
+        let result = trigger("", &["\"0 0 8 * * *\"", "\"0 0 25 * * * *\""]).await;
+        let Err(AncymonError::ConfigError(ConfigError::InvalidValue(message))) = result else {
+            panic!("expected invalid value");
+        };
+        assert!(message.contains("cron-1"), "{message}");
+        assert!(trigger("", &["42"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn closed_queue() {
+        let mut trigger = trigger("", &["\"* * * * * * *\""]).await.unwrap();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        drop(rx);
+        tokio::time::timeout(Duration::from_secs(5), trigger.run(tx))
+            .await
+            .unwrap();
     }
 }
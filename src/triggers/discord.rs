@@ -0,0 +1,388 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde::Deserialize;
+use serenity::all::{Context, GatewayIntents, Message};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::{
+    errors::{AncymonError, BuildError, ConfigError},
+    events::Event,
+    triggers::{errors, Trigger, TriggerSource},
+    values::Value,
+};
+
+const GATEWAY_QUEUE_SIZE: usize = 64;
+
+#[derive(Debug, Default, Deserialize)]
+struct DiscordConfig {
+    token: String,
+}
+
+/// Gateway message reduced to the fields used by the triggers.
+#[derive(Clone, Debug, Default)]
+struct DiscordMessage {
+    id: u64,
+    channel: u64,
+    guild: Option<u64>,
+    author_id: u64,
+    author_name: String,
+    author_bot: bool,
+    content: String,
+}
+impl From<Message> for DiscordMessage {
+    fn from(value: Message) -> Self {
+        Self {
+            id: value.id.get(),
+            channel: value.channel_id.get(),
+            guild: value.guild_id.map(|a| a.get()),
+            author_id: value.author.id.get(),
+            author_name: value.author.name,
+            author_bot: value.author.bot,
+            content: value.content,
+        }
+    }
+}
+impl From<&DiscordMessage> for Value {
+    fn from(value: &DiscordMessage) -> Self {
+        // Discord snowflakes use only 63 bits, so they fit in an i64.
+        let author = Value::Map(HashMap::from_iter(vec![
+            ("id".to_string(), Value::Integer(value.author_id as i64)),
+            ("name".to_string(), Value::String(value.author_name.clone())),
+        ]));
+        Value::Map(HashMap::from_iter(vec![
+            ("author".to_string(), author),
+            ("channel".to_string(), Value::Integer(value.channel as i64)),
+            (
+                "guild".to_string(),
+                value
+                    .guild
+                    .map(|a| Value::Integer(a as i64))
+                    .unwrap_or_default(),
+            ),
+            ("content".to_string(), Value::String(value.content.clone())),
+            ("message_id".to_string(), Value::Integer(value.id as i64)),
+        ]))
+    }
+}
+
+/// Per trigger message filter. All the specified conditions have to match.
+#[derive(Debug, Default)]
+struct MessageFilter {
+    channel: Option<u64>,
+    command: Option<String>,
+    pattern: Option<Regex>,
+}
+impl MessageFilter {
+    fn matches(&self, message: &DiscordMessage) -> bool {
+        // Never react to bots, including our own handler output.
+        if message.author_bot {
+            return false;
+        }
+        if let Some(channel) = self.channel
+            && channel != message.channel
+        {
+            return false;
+        }
+        if let Some(command) = &self.command {
+            let mut words = message.content.split_whitespace();
+            if words.next() != Some(command.as_str()) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern
+            && !pattern.is_match(&message.content)
+        {
+            return false;
+        }
+        true
+    }
+}
+impl TryFrom<&toml::Value> for MessageFilter {
+    type Error = AncymonError;
+
+    fn try_from(value: &toml::Value) -> Result<Self, Self::Error> {
+        let table = value.as_table().ok_or(ConfigError::InvalidValueType(
+            "Discord arguments: expected table".to_string(),
+        ))?;
+
+        let channel = if let Some(v) = table.get("channel") {
+            let id = v.as_integer().ok_or(ConfigError::InvalidValueType(
+                "Discord arguments: expected integer for `channel`".to_string(),
+            ))?;
+            Some(u64::try_from(id).map_err(|_| {
+                ConfigError::InvalidValue(format!("Discord arguments: invalid channel id {id}"))
+            })?)
+        } else {
+            None
+        };
+        let command = if let Some(v) = table.get("command") {
+            Some(
+                v.as_str()
+                    .ok_or(ConfigError::InvalidValueType(
+                        "Discord arguments: expected string for `command`".to_string(),
+                    ))?
+                    .to_string(),
+            )
+        } else {
+            None
+        };
+        let pattern = if let Some(v) = table.get("pattern") {
+            let pat = v.as_str().ok_or(ConfigError::InvalidValueType(
+                "Discord arguments: expected string for `pattern`".to_string(),
+            ))?;
+            Some(Regex::new(pat).map_err(|e| {
+                ConfigError::InvalidValue(format!("Discord arguments: invalid pattern. {e}"))
+            })?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            channel,
+            command,
+            pattern,
+        })
+    }
+}
+
+/// Forwards gateway messages to the trigger.
+struct Gateway {
+    tx: Sender<DiscordMessage>,
+}
+#[serenity::async_trait]
+impl serenity::client::EventHandler for Gateway {
+    async fn message(&self, _ctx: Context, new_message: Message) {
+        if self.tx.send(new_message.into()).await.is_err() {
+            tracing::warn!("Discord trigger is not listening, message dropped");
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DiscordTrigger {
+    config: DiscordConfig,
+    filters: Vec<MessageFilter>,
+    triggers: Vec<Trigger>,
+}
+impl DiscordTrigger {
+    /// Emit events for every received message, until either side of the channels closes.
+    async fn listen(&self, mut messages: Receiver<DiscordMessage>, tx: Sender<Event>) {
+        while let Some(message) = messages.recv().await {
+            let value = Value::from(&message);
+            for (filter, trigger) in self.filters.iter().zip(self.triggers.iter()) {
+                if !filter.matches(&message) {
+                    continue;
+                }
+                if tx
+                    .send(Event::new(trigger.emit.to_string(), Ok(value.clone())))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TriggerSource for DiscordTrigger {
+    async fn init(
+        &mut self,
+        config: &toml::Table,
+        triggers: Vec<Trigger>,
+    ) -> Result<(), AncymonError> {
+        if triggers.is_empty() {
+            return Err(
+                ConfigError::MissingValue("No discord triggers specified".to_string()).into(),
+            );
+        }
+        self.config = parse_config(config)?;
+
+        self.filters = triggers
+            .iter()
+            .map(|a| MessageFilter::try_from(&a.arguments))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.triggers = triggers;
+
+        Ok(())
+    }
+    fn check(config: &toml::Table, triggers: &[Trigger]) -> Vec<String> {
+        let mut problems = errors([parse_config(config)]);
+        problems.extend(errors(
+            triggers
+                .iter()
+                .map(|a| MessageFilter::try_from(&a.arguments)),
+        ));
+        problems
+    }
+    async fn run(&mut self, tx: Sender<Event>) {
+        let intents = GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT;
+        let (gateway_tx, gateway_rx) = tokio::sync::mpsc::channel(GATEWAY_QUEUE_SIZE);
+
+        let mut client = match serenity::Client::builder(&self.config.token, intents)
+            .event_handler(Gateway { tx: gateway_tx })
+            .await
+        {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("Discord client could not be created: {e}");
+                return;
+            }
+        };
+
+        tokio::select! {
+            result = client.start() => {
+                if let Err(e) = result {
+                    tracing::error!("Discord gateway connection failed: {e}");
+                }
+            }
+            _ = self.listen(gateway_rx, tx) => {}
+        }
+    }
+}
+
+fn parse_config(config: &toml::Table) -> Result<DiscordConfig, AncymonError> {
+    config.clone().try_into().map_err(|e: toml::de::Error| {
+        BuildError::Source {
+            message: "Invalid discord source config".to_string(),
+            source: Some(Arc::new(e)),
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    async fn trigger(arguments: &[&str]) -> DiscordTrigger {
+        let config = toml::Table::from_str("token = \"test\"").unwrap();
+        let triggers = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Trigger {
+                source: "discord".to_string(),
+                emit: format!("discord-{i}"),
+                arguments: toml::Value::from_str(a).unwrap(),
+            })
+            .collect();
+        let mut trigger = DiscordTrigger::default();
+        trigger.init(&config, triggers).await.unwrap();
+        trigger
+    }
+
+    fn message(channel: u64, content: &str) -> DiscordMessage {
+        DiscordMessage {
+            id: 1,
+            channel,
+            guild: Some(5),
+            author_id: 7,
+            author_name: "ancymon".to_string(),
+            author_bot: false,
+            content: content.to_string(),
+        }
+    }
+
+    /// Feed the messages through a stand-in gateway channel and collect emitted events.
+    async fn emitted(trigger: DiscordTrigger, messages: Vec<DiscordMessage>) -> Vec<Event> {
+        let (gateway_tx, gateway_rx) = tokio::sync::mpsc::channel(GATEWAY_QUEUE_SIZE);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(GATEWAY_QUEUE_SIZE);
+        for message in messages {
+            gateway_tx.send(message).await.unwrap();
+        }
+        drop(gateway_tx);
+        trigger.listen(gateway_rx, tx).await;
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn message_value() {
+        let trigger = trigger(&["{}"]).await;
+        let events = emitted(trigger, vec![message(3, "hello")]).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "discord-0");
+
+        let value = events[0].value.clone().unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map["channel"], Value::Integer(3));
+        assert_eq!(map["guild"], Value::Integer(5));
+        assert_eq!(map["content"], Value::String("hello".to_string()));
+        assert_eq!(map["message_id"], Value::Integer(1));
+        assert_eq!(
+            map["author"].as_map().unwrap()["name"],
+            Value::String("ancymon".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn filter_channel() {
+        let trigger = trigger(&["{ channel = 3 }"]).await;
+        let events = emitted(trigger, vec![message(2, "a"), message(3, "b")]).await;
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].value.as_ref().unwrap().as_map().unwrap()["content"],
+            Value::String("b".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn filter_command() {
+        let trigger = trigger(&["{ command = \"!temp\" }"]).await;
+        let events = emitted(
+            trigger,
+            vec![
+                message(1, "!temp attic"),
+                message(1, "!temperature"),
+                message(1, "show !temp"),
+                message(1, "!temp"),
+            ],
+        )
+        .await;
+        assert_eq!(events.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn filter_pattern() {
+        let trigger = trigger(&["{ pattern = \"^[0-9]+$\" }", "{}"]).await;
+        let events = emitted(trigger, vec![message(1, "123"), message(1, "12a")]).await;
+        let names = events.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["discord-0", "discord-1", "discord-1"]);
+    }
+
+    #[tokio::test]
+    async fn ignore_bots() {
+        let trigger = trigger(&["{}"]).await;
+        let mut bot_message = message(1, "beep");
+        bot_message.author_bot = true;
+        let events = emitted(trigger, vec![bot_message]).await;
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_pattern() {
+        let config = toml::Table::from_str("token = \"test\"").unwrap();
+        let mut trigger = DiscordTrigger::default();
+        let result = trigger
+            .init(
+                &config,
+                vec![Trigger {
+                    source: "discord".to_string(),
+                    emit: "discord".to_string(),
+                    arguments: toml::Value::from_str("{ pattern = \"(\" }").unwrap(),
+                }],
+            )
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::{sync::mpsc::Sender, time::Instant};
+
+use crate::{
+    config::deserialize_duration,
+    errors::{AncymonError, ConfigError},
+    events::Event,
+    triggers::{errors, Trigger, TriggerSource},
+    values::Value,
+};
+
+#[derive(Debug, Default, Deserialize)]
+struct IntervalArguments {
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    every: Option<Duration>,
+    /// Random delay up to this duration added to every tick.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    jitter: Option<Duration>,
+    /// Delay before the first tick, by default the first tick fires after one interval.
+    #[serde(
+        rename = "start-offset",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    start_offset: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Schedule {
+    every: Duration,
+    jitter: Duration,
+    /// Next tick without jitter, so the jitter does not accumulate.
+    next: Instant,
+    /// Next tick including the jitter.
+    deadline: Instant,
+}
+impl Schedule {
+    fn jittered(&self, tick: Instant) -> Instant {
+        if self.jitter.is_zero() {
+            return tick;
+        }
+        tick + self.jitter.mul_f64(rand::random::<f64>())
+    }
+    fn advance(&mut self) {
+        self.next += self.every;
+        // Skip the ticks missed while the runtime was stalled.
+        let now = Instant::now();
+        if self.next < now {
+            let missed = (now - self.next).as_secs_f64() / self.every.as_secs_f64();
+            self.next += self.every.mul_f64(missed.ceil());
+        }
+        self.deadline = self.jittered(self.next);
+    }
+}
+
+/// Fires at a fixed interval, eg. `arguments = "90s"`, `arguments = 90` or
+/// `arguments = { every = "90s", jitter = "5s", start-offset = "10s" }`.
+/// Emits the tick timestamp, like [`CronTrigger`](crate::triggers::cron::CronTrigger).
+#[derive(Default)]
+pub struct IntervalTrigger {
+    arguments: Vec<IntervalArguments>,
+    triggers: Vec<Trigger>,
+}
+
+#[async_trait]
+impl TriggerSource for IntervalTrigger {
+    async fn init(
+        &mut self,
+        _config: &toml::Table,
+        triggers: Vec<Trigger>,
+    ) -> Result<(), AncymonError> {
+        if triggers.is_empty() {
+            return Err(
+                ConfigError::MissingValue("No interval triggers specified".to_string()).into(),
+            );
+        }
+
+        self.arguments = triggers
+            .iter()
+            .map(parse_arguments)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.triggers = triggers;
+        Ok(())
+    }
+    fn check(_config: &toml::Table, triggers: &[Trigger]) -> Vec<String> {
+        errors(triggers.iter().map(parse_arguments))
+    }
+    async fn run(&mut self, tx: Sender<Event>) {
+        let start = Instant::now();
+        let mut schedules = self
+            .arguments
+            .iter()
+            .map(|a| {
+                let every = a.every.unwrap_or_default();
+                let mut schedule = Schedule {
+                    every,
+                    jitter: a.jitter.unwrap_or_default(),
+                    next: start + a.start_offset.unwrap_or(every),
+                    deadline: start,
+                };
+                schedule.deadline = schedule.jittered(schedule.next);
+                schedule
+            })
+            .collect::<Vec<_>>();
+
+        loop {
+            let Some(deadline) = schedules.iter().map(|a| a.deadline).min() else {
+                return;
+            };
+            tokio::time::sleep_until(deadline).await;
+
+            let value = Value::Integer(Utc::now().timestamp());
+            for (schedule, trigger) in schedules.iter_mut().zip(self.triggers.iter()) {
+                if schedule.deadline > deadline {
+                    continue;
+                }
+                schedule.advance();
+                if tx
+                    .send(Event::new(trigger.emit.to_string(), Ok(value.clone())))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn parse_arguments(trigger: &Trigger) -> Result<IntervalArguments, AncymonError> {
+    let error = |e: toml::de::Error| {
+        ConfigError::InvalidValue(format!("Interval arguments for `{}`: {e}", trigger.emit))
+    };
+    let arguments = match &trigger.arguments {
+        a @ toml::Value::Table(_) => a.clone().try_into().map_err(error)?,
+        a => IntervalArguments {
+            every: deserialize_duration(a.clone()).map_err(error)?,
+            ..Default::default()
+        },
+    };
+    match arguments.every {
+        Some(every) if !every.is_zero() => Ok(arguments),
+        _ => Err(ConfigError::InvalidValue(format!(
+            "Interval arguments for `{}`: expected a positive `every` duration",
+            trigger.emit
+        ))
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn triggers(arguments: &[&str]) -> Vec<Trigger> {
+        arguments
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Trigger {
+                source: "interval".to_string(),
+                emit: format!("tick-{i}"),
+                arguments: toml::Value::from_str(a).unwrap(),
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks() {
+        let mut trigger = IntervalTrigger::default();
+        trigger
+            .init(
+                &toml::Table::new(),
+                triggers(&["\"90s\"", "{ every = 60, start-offset = 0 }"]),
+            )
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let start = Instant::now();
+        tokio::spawn(async move { trigger.run(tx).await });
+
+        let mut fired = Vec::new();
+        for _ in 0..5 {
+            let event = rx.recv().await.unwrap();
+            assert!(matches!(event.value, Ok(Value::Integer(_))));
+            fired.push((event.name, (Instant::now() - start).as_secs()));
+        }
+        assert_eq!(
+            fired,
+            vec![
+                ("tick-1".to_string(), 0),
+                ("tick-1".to_string(), 60),
+                ("tick-0".to_string(), 90),
+                ("tick-1".to_string(), 120),
+                ("tick-0".to_string(), 180),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn jitter() {
+        let mut trigger = IntervalTrigger::default();
+        trigger
+            .init(
+                &toml::Table::new(),
+                triggers(&["{ every = \"10s\", jitter = \"2s\" }"]),
+            )
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let start = Instant::now();
+        tokio::spawn(async move { trigger.run(tx).await });
+
+        for i in 1..=5 {
+            rx.recv().await.unwrap();
+            let elapsed = Instant::now() - start;
+            assert!(elapsed >= Duration::from_secs(10 * i));
+            assert!(elapsed <= Duration::from_secs(10 * i + 2));
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments() {
+        for arguments in [
+            "\"soon\"",
+            "\"0s\"",
+            "{ jitter = 1 }",
+            "{ every = \"1x\" }",
+            "[]",
+        ] {
+            let mut trigger = IntervalTrigger::default();
+            let result = trigger
+                .init(&toml::Table::new(), triggers(&[arguments]))
+                .await;
+            assert!(result.is_err(), "{arguments}");
+        }
+    }
+}
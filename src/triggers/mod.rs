@@ -1,12 +1,18 @@
 use async_trait::async_trait;
 use serde::Deserialize;
 
-use crate::{errors::AncymonError, events::Event};
+use crate::{
+    errors::{report, AncymonError},
+    events::Event,
+};
 
 pub mod cron;
 pub mod discord;
+pub mod interval;
+pub mod once;
+pub mod webhook;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct Trigger {
     pub source: String,
     pub(crate) emit: String,
@@ -21,4 +27,25 @@ pub trait TriggerSource {
         triggers: Vec<Trigger>,
     ) -> Result<(), AncymonError>;
     async fn run(&mut self, tx: tokio::sync::mpsc::Sender<Event>);
+    /// Problems in the source config and trigger arguments, found without initializing
+    /// the source. Reported by [`crate::Bot::validate`].
+    #[allow(unused_variables)]
+    fn check(config: &toml::Table, triggers: &[Trigger]) -> Vec<String>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+}
+
+/// [`TriggerSource::check`] of a registered source type.
+pub(crate) type SourceCheck = fn(&toml::Table, &[Trigger]) -> Vec<String>;
+
+/// Messages of the failed results.
+pub(crate) fn errors<T>(results: impl IntoIterator<Item = Result<T, AncymonError>>) -> Vec<String> {
+    results
+        .into_iter()
+        .filter_map(|a| a.err())
+        .map(|e| report(&e))
+        .collect()
 }
@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{collections::HashSet, time::Duration};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    config::deserialize_duration,
+    errors::{AncymonError, ConfigError},
+    events::Event,
+    triggers::{errors, Trigger, TriggerSource},
+    values::{parse_datetime, Value},
+};
+
+#[derive(Debug, Deserialize)]
+struct OnceArguments {
+    /// RFC 3339 time.
+    at: Option<String>,
+    /// Delay after the source starts.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    after: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Moment {
+    At(DateTime<Utc>),
+    After(Duration),
+}
+
+/// Fires once, either at a given time, `arguments = { at = "2026-01-01T08:00:00Z" }`,
+/// or after startup, `arguments = { after = "10s" }`. Times already in the past are skipped.
+/// Emits the firing timestamp, like [`CronTrigger`](crate::triggers::cron::CronTrigger).
+///
+/// Triggers that already fired are not fired again when the source is restarted
+/// by a config reload, unless their event or arguments change.
+#[derive(Default)]
+pub struct OnceTrigger {
+    moments: Vec<Moment>,
+    triggers: Vec<Trigger>,
+    /// Keys of the triggers fired so far, kept across `init` calls.
+    fired: HashSet<String>,
+}
+
+#[async_trait]
+impl TriggerSource for OnceTrigger {
+    async fn init(
+        &mut self,
+        _config: &toml::Table,
+        triggers: Vec<Trigger>,
+    ) -> Result<(), AncymonError> {
+        if triggers.is_empty() {
+            return Err(ConfigError::MissingValue("No once triggers specified".to_string()).into());
+        }
+
+        self.moments = triggers
+            .iter()
+            .map(parse_arguments)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.triggers = triggers;
+        Ok(())
+    }
+    fn check(_config: &toml::Table, triggers: &[Trigger]) -> Vec<String> {
+        errors(triggers.iter().map(parse_arguments))
+    }
+    async fn run(&mut self, tx: Sender<Event>) {
+        let start = Utc::now();
+        let mut pending = self
+            .moments
+            .iter()
+            .zip(self.triggers.iter())
+            .filter(|(_, trigger)| {
+                let fired = self.fired.contains(&key(trigger));
+                if fired {
+                    tracing::info!("Skipping once trigger `{}` already fired", trigger.emit);
+                }
+                !fired
+            })
+            .filter_map(|(moment, trigger)| {
+                let time = match moment {
+                    Moment::At(time) => *time,
+                    Moment::After(delay) => start + *delay,
+                };
+                if time < start {
+                    tracing::info!(
+                        "Skipping once trigger `{}` scheduled in the past",
+                        trigger.emit
+                    );
+                    return None;
+                }
+                Some((time, trigger.clone()))
+            })
+            .collect::<Vec<_>>();
+        pending.sort_by_key(|a| a.0);
+
+        for (time, trigger) in pending {
+            if let Ok(delay) = (time - Utc::now()).to_std() {
+                tokio::time::sleep(delay).await;
+            }
+            if tx
+                .send(Event::new(
+                    trigger.emit.to_string(),
+                    Ok(Value::Integer(time.timestamp())),
+                ))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            self.fired.insert(key(&trigger));
+        }
+    }
+}
+
+/// Identifies a trigger across config reloads.
+fn key(trigger: &Trigger) -> String {
+    format!("{}:{}", trigger.emit, trigger.arguments)
+}
+
+fn parse_arguments(trigger: &Trigger) -> Result<Moment, AncymonError> {
+    let error = |e: String| {
+        ConfigError::InvalidValue(format!("Once arguments for `{}`: {e}", trigger.emit))
+    };
+    let arguments: OnceArguments = trigger
+        .arguments
+        .clone()
+        .try_into()
+        .map_err(|e: toml::de::Error| error(e.to_string()))?;
+
+    match (arguments.at, arguments.after) {
+        (Some(at), None) => Ok(Moment::At(
+            parse_datetime(&at).ok_or(error(format!("invalid time `{at}`")))?,
+        )),
+        (None, Some(after)) => Ok(Moment::After(after)),
+        _ => Err(error("expected either `at` or `after`".to_string()).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn triggers(arguments: &[String]) -> Vec<Trigger> {
+        arguments
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Trigger {
+                source: "once".to_string(),
+                emit: format!("once-{i}"),
+                arguments: toml::Value::from_str(a).unwrap(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fire_once() {
+        let soon = Utc::now() + Duration::from_millis(50);
+        let mut trigger = OnceTrigger::default();
+        trigger
+            .init(
+                &toml::Table::new(),
+                triggers(&[
+                    format!("{{ at = \"{}\" }}", soon.to_rfc3339()),
+                    "{ after = \"10ms\" }".to_string(),
+                    "{ at = \"2020-01-01T00:00:00Z\" }".to_string(),
+                ]),
+            )
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        trigger.run(tx).await;
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.name, "once-1");
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second.name, "once-0");
+        assert_eq!(second.value.unwrap(), Value::Integer(soon.timestamp()));
+        assert!(Utc::now() >= soon);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn restart() {
+        let mut trigger = OnceTrigger::default();
+        let arguments = ["{ after = \"1ms\" }".to_string()];
+        trigger
+            .init(&toml::Table::new(), triggers(&arguments))
+            .await
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        trigger.run(tx).await;
+        assert_eq!(rx.recv().await.unwrap().name, "once-0");
+
+        // Initialized again with the same trigger, as on a config reload.
+        trigger
+            .init(&toml::Table::new(), triggers(&arguments))
+            .await
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        trigger.run(tx).await;
+        assert!(rx.recv().await.is_none());
+
+        // Changed arguments make it a new trigger.
+        trigger
+            .init(
+                &toml::Table::new(),
+                triggers(&["{ after = \"2ms\" }".to_string()]),
+            )
+            .await
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        trigger.run(tx).await;
+        assert_eq!(rx.recv().await.unwrap().name, "once-0");
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments() {
+        for arguments in [
+            "{}",
+            "{ at = \"tomorrow\" }",
+            "{ at = \"2020-01-01T00:00:00Z\", after = 1 }",
+            "\"10s\"",
+        ] {
+            let mut trigger = OnceTrigger::default();
+            let result = trigger
+                .init(&toml::Table::new(), triggers(&[arguments.to_string()]))
+                .await;
+            assert!(result.is_err(), "{arguments}");
+        }
+    }
+}
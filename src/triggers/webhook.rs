@@ -0,0 +1,461 @@
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    config::deserialize_duration,
+    errors::{AncymonError, BuildError, ConfigError},
+    events::{Event, EventValue, Reply},
+    triggers::{errors, Trigger, TriggerSource},
+    values::Value,
+};
+
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Deserialize)]
+struct WebhookConfig {
+    /// Listening address, eg. `"0.0.0.0:8080"`.
+    bind: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookArguments {
+    path: String,
+    #[serde(default = "default_method")]
+    method: String,
+    /// Wait for this event and use its value as the response.
+    #[serde(rename = "response-event")]
+    response_event: Option<String>,
+    #[serde(
+        rename = "response-timeout",
+        default,
+        deserialize_with = "deserialize_duration"
+    )]
+    response_timeout: Option<Duration>,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+#[derive(Clone, Debug)]
+struct Route {
+    path: String,
+    method: Method,
+    emit: String,
+    response_event: Option<String>,
+    response_timeout: Duration,
+}
+impl TryFrom<&Trigger> for Route {
+    type Error = AncymonError;
+
+    fn try_from(trigger: &Trigger) -> Result<Self, Self::Error> {
+        let arguments: WebhookArguments = trigger
+            .arguments
+            .clone()
+            .try_into()
+            .map_err(|e| ConfigError::InvalidValue(format!("Webhook arguments: {e}")))?;
+        if !arguments.path.starts_with('/') {
+            return Err(ConfigError::InvalidValue(format!(
+                "Webhook arguments: path has to start with `/`: {}",
+                arguments.path
+            ))
+            .into());
+        }
+        let method = Method::from_bytes(arguments.method.to_uppercase().as_bytes())
+            .map_err(|e| ConfigError::InvalidValue(format!("Webhook arguments: {e}")))?;
+
+        Ok(Self {
+            path: arguments.path,
+            method,
+            emit: trigger.emit.to_string(),
+            response_event: arguments.response_event,
+            response_timeout: arguments
+                .response_timeout
+                .unwrap_or(DEFAULT_RESPONSE_TIMEOUT),
+        })
+    }
+}
+
+struct WebhookState {
+    routes: Vec<Route>,
+    tx: Sender<Event>,
+}
+
+/// Runs an HTTP server, emitting an event for every request matching a trigger.
+///
+/// The event value is a map with `method`, `path`, `query`, `headers` and `body`.
+/// JSON and form bodies are parsed, other bodies are passed as text.
+/// Without `response-event` the request is answered with `202 Accepted` right away.
+#[derive(Default)]
+pub struct WebhookTrigger {
+    config: WebhookConfig,
+    routes: Vec<Route>,
+}
+impl WebhookTrigger {
+    fn router(&self, tx: Sender<Event>) -> Router {
+        let state = WebhookState {
+            routes: self.routes.clone(),
+            tx,
+        };
+        Router::new().fallback(handle).with_state(Arc::new(state))
+    }
+}
+
+#[async_trait]
+impl TriggerSource for WebhookTrigger {
+    async fn init(
+        &mut self,
+        config: &toml::Table,
+        triggers: Vec<Trigger>,
+    ) -> Result<(), AncymonError> {
+        if triggers.is_empty() {
+            return Err(
+                ConfigError::MissingValue("No webhook triggers specified".to_string()).into(),
+            );
+        }
+        self.config = parse_config(config)?;
+        self.routes = triggers
+            .iter()
+            .map(Route::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+    fn check(config: &toml::Table, triggers: &[Trigger]) -> Vec<String> {
+        let mut problems = errors([parse_config(config)]);
+        problems.extend(errors(triggers.iter().map(Route::try_from)));
+        problems
+    }
+    async fn run(&mut self, tx: Sender<Event>) {
+        let bind = self
+            .config
+            .bind
+            .as_deref()
+            .unwrap_or(DEFAULT_BIND)
+            .to_string();
+        let listener = match tokio::net::TcpListener::bind(&bind).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Webhook server could not bind {bind}: {e}");
+                return;
+            }
+        };
+        tracing::info!("Webhook server listening on {bind}");
+        if let Err(e) = axum::serve(listener, self.router(tx)).await {
+            tracing::error!("Webhook server failed: {e}");
+        }
+    }
+}
+
+fn parse_config(config: &toml::Table) -> Result<WebhookConfig, AncymonError> {
+    config.clone().try_into().map_err(|e: toml::de::Error| {
+        BuildError::Source {
+            message: "Invalid webhook source config".to_string(),
+            source: Some(Arc::new(e)),
+        }
+        .into()
+    })
+}
+
+async fn handle(
+    State(state): State<Arc<WebhookState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let routes = state
+        .routes
+        .iter()
+        .filter(|a| a.path == uri.path())
+        .collect::<Vec<_>>();
+    if routes.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let routes = routes
+        .into_iter()
+        .filter(|a| a.method == method)
+        .collect::<Vec<_>>();
+    if routes.is_empty() {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    let value = match request_value(&method, &uri, &headers, &body) {
+        Ok(value) => value,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    // Only the first route waiting for a response answers the request.
+    let mut response = None;
+    for route in routes {
+        let mut event = Event::new(route.emit.to_string(), Ok(value.clone()));
+        if let Some(response_event) = &route.response_event
+            && response.is_none()
+        {
+            let (reply, rx) = Reply::new(response_event.to_string());
+            event = event.with_reply(reply);
+            response = Some((rx, route.response_timeout));
+        }
+        if state.tx.send(event).await.is_err() {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    }
+
+    let Some((rx, timeout)) = response else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(value)) => response_value(value),
+        // The pipeline finished without emitting the response event.
+        Ok(Err(_)) => StatusCode::NO_CONTENT.into_response(),
+        Err(_) => StatusCode::GATEWAY_TIMEOUT.into_response(),
+    }
+}
+
+fn request_value(
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Value, String> {
+    let query = serde_urlencoded::from_str::<Vec<(String, String)>>(uri.query().unwrap_or(""))
+        .map_err(|e| format!("Invalid query: {e}"))?;
+    let headers = headers
+        .iter()
+        .map(|(k, v)| {
+            let v = String::from_utf8_lossy(v.as_bytes()).to_string();
+            (k.to_string(), Value::String(v))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let content_type = headers
+        .get(CONTENT_TYPE.as_str())
+        .and_then(|a| a.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let body = if body.is_empty() {
+        Value::Null
+    } else if content_type.contains("json") {
+        serde_json::from_slice::<serde_json::Value>(body)
+            .map_err(|e| format!("Invalid JSON body: {e}"))?
+            .into()
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        strings(
+            serde_urlencoded::from_bytes::<Vec<(String, String)>>(body)
+                .map_err(|e| format!("Invalid form body: {e}"))?,
+        )
+    } else {
+        match std::str::from_utf8(body) {
+            Ok(s) => Value::String(s.to_string()),
+            Err(_) => Value::Bytes(body.to_vec()),
+        }
+    };
+
+    Ok(Value::Map(HashMap::from_iter(vec![
+        ("method".to_string(), Value::String(method.to_string())),
+        ("path".to_string(), Value::String(uri.path().to_string())),
+        ("query".to_string(), strings(query)),
+        ("headers".to_string(), Value::Map(headers)),
+        ("body".to_string(), body),
+    ])))
+}
+
+fn strings(pairs: Vec<(String, String)>) -> Value {
+    Value::Map(
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k, Value::String(v)))
+            .collect(),
+    )
+}
+
+fn response_value(value: EventValue) -> Response {
+    match value {
+        Ok(Value::Null) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Value::String(s)) => s.into_response(),
+        Ok(Value::Bytes(b)) => b.into_response(),
+        Ok(v) => Json(serde_json::Value::from(&v)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}")).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use tokio::sync::mpsc::Receiver;
+
+    use super::*;
+
+    /// Serve the triggers on a local port, returning the url and the emitted events.
+    async fn server(arguments: &[&str]) -> (String, Receiver<Event>) {
+        let triggers = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Trigger {
+                source: "webhook".to_string(),
+                emit: format!("hook-{i}"),
+                arguments: toml::Value::from_str(a).unwrap(),
+            })
+            .collect();
+        let mut trigger = WebhookTrigger::default();
+        trigger.init(&toml::Table::new(), triggers).await.unwrap();
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = trigger.router(tx);
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn json_request() {
+        let (url, mut rx) = server(&["{ path = \"/deploy\" }"]).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{url}/deploy?branch=main"))
+            .header("x-ci", "github")
+            .json(&serde_json::json!({ "commit": "abc", "ok": true }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.name, "hook-0");
+        let value = event.value.unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map["method"], Value::String("POST".to_string()));
+        assert_eq!(map["path"], Value::String("/deploy".to_string()));
+        assert_eq!(
+            map["query"].as_map().unwrap()["branch"],
+            Value::String("main".to_string())
+        );
+        assert_eq!(
+            map["headers"].as_map().unwrap()["x-ci"],
+            Value::String("github".to_string())
+        );
+        assert_eq!(
+            map["body"].as_map().unwrap()["commit"],
+            Value::String("abc".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn form_request() {
+        let (url, mut rx) = server(&["{ path = \"/form\", method = \"put\" }"]).await;
+
+        reqwest::Client::new()
+            .put(format!("{url}/form"))
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body("name=attic&temp=23.5")
+            .send()
+            .await
+            .unwrap();
+
+        let value = rx.recv().await.unwrap().value.unwrap();
+        let body = value.as_map().unwrap()["body"].clone();
+        assert_eq!(
+            body.as_map().unwrap()["temp"],
+            Value::String("23.5".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn routing() {
+        let (url, _rx) = server(&["{ path = \"/a\", method = \"GET\" }"]).await;
+        let client = reqwest::Client::new();
+
+        let status = |r: reqwest::Response| r.status().as_u16();
+        assert_eq!(
+            status(client.get(format!("{url}/a")).send().await.unwrap()),
+            202
+        );
+        assert_eq!(
+            status(client.post(format!("{url}/a")).send().await.unwrap()),
+            405
+        );
+        assert_eq!(
+            status(client.get(format!("{url}/b")).send().await.unwrap()),
+            404
+        );
+        assert_eq!(
+            status(
+                client
+                    .get(format!("{url}/a"))
+                    .header(CONTENT_TYPE, "application/json")
+                    .body("{")
+                    .send()
+                    .await
+                    .unwrap()
+            ),
+            400
+        );
+    }
+
+    #[tokio::test]
+    async fn wait_for_response() {
+        let (url, mut rx) = server(&[
+            "{ path = \"/sum\", response-event = \"summed\" }",
+            "{ path = \"/none\", response-event = \"summed\" }",
+            "{ path = \"/slow\", response-event = \"summed\", response-timeout = \"10ms\" }",
+        ])
+        .await;
+
+        // Stand-in for the bot, answering the first request and ignoring the others.
+        tokio::spawn(async move {
+            let mut pending = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if event.name == "hook-0" {
+                    let child = event.child("summed".to_string(), Ok(Value::Integer(3)));
+                    event.reply.as_ref().unwrap().offer(&child);
+                } else if event.name == "hook-2" {
+                    pending.push(event);
+                }
+            }
+        });
+
+        let client = reqwest::Client::new();
+        let response = client.post(format!("{url}/sum")).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "3");
+
+        let response = client.post(format!("{url}/none")).send().await.unwrap();
+        assert_eq!(response.status(), 204);
+
+        let response = client.post(format!("{url}/slow")).send().await.unwrap();
+        assert_eq!(response.status(), 504);
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments() {
+        for arguments in [
+            "{ method = \"GET\" }",
+            "{ path = \"relative\" }",
+            "{ path = \"/a\", method = \"NOT A METHOD\" }",
+        ] {
+            let mut trigger = WebhookTrigger::default();
+            let result = trigger
+                .init(
+                    &toml::Table::new(),
+                    vec![Trigger {
+                        source: "webhook".to_string(),
+                        emit: "hook".to_string(),
+                        arguments: toml::Value::from_str(arguments).unwrap(),
+                    }],
+                )
+                .await;
+            assert!(result.is_err(), "{arguments}");
+        }
+    }
+}
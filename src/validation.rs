@@ -0,0 +1,402 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    config::{parsing_error, Config},
+    limits::Limits,
+    triggers::SourceCheck,
+};
+
+/// Every problem found in a config by [`crate::Bot::validate`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    /// Problems that prevent the bot from starting.
+    pub errors: Vec<String>,
+    /// Likely mistakes, the bot starts anyway.
+    pub warnings: Vec<String>,
+}
+impl Diagnostics {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in self.errors.iter() {
+            writeln!(f, "error: {error}")?;
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the config against the registered handler and source types,
+/// and the event graph formed by the triggers and actions.
+/// `source_check` returns the check of a registered source type.
+pub(crate) fn validate(
+    config: &Config,
+    handler_type: impl Fn(&str) -> bool,
+    source_check: impl Fn(&str) -> Option<SourceCheck>,
+) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+    check_handlers(config, &handler_type, &mut diagnostics);
+    check_sources(config, &source_check, &mut diagnostics);
+    check_events(config, &mut diagnostics);
+    diagnostics
+}
+
+fn check_handlers(config: &Config, handler_type: &impl Fn(&str) -> bool, d: &mut Diagnostics) {
+    let handlers = BTreeMap::from_iter(config.handlers.iter());
+    for (name, handler_config) in handlers {
+        match handler_config.get("type").map(|a| a.as_str()) {
+            None => d
+                .errors
+                .push(format!("handler `{name}`: missing key `type`")),
+            Some(None) => d
+                .errors
+                .push(format!("handler `{name}`: expected string for key `type`")),
+            Some(Some(t)) if !handler_type(t) => d
+                .errors
+                .push(format!("handler `{name}`: unknown handler type `{t}`")),
+            _ => {}
+        }
+        if let Err(e) = handler_config.clone().try_into::<Limits>() {
+            d.errors.push(format!(
+                "handler `{name}`: invalid limits: {}",
+                parsing_error(None, e)
+            ));
+        }
+    }
+    for action in config.actions.iter() {
+        if !config.handlers.contains_key(&action.handler) {
+            d.errors.push(format!(
+                "action `{}`: handler `{}` is not defined",
+                action.name(),
+                action.handler
+            ));
+        }
+    }
+}
+
+fn check_sources(
+    config: &Config,
+    source_check: &impl Fn(&str) -> Option<SourceCheck>,
+    d: &mut Diagnostics,
+) {
+    let sources = BTreeSet::from_iter(config.triggers.iter().map(|a| a.source.as_str()));
+    for source in sources {
+        let Some(check) = source_check(source) else {
+            d.errors.push(format!("unknown source type `{source}`"));
+            continue;
+        };
+        let Some(source_config) = config.sources.get(source) else {
+            d.errors
+                .push(format!("source `{source}` has triggers but no config"));
+            continue;
+        };
+        let triggers = Vec::from_iter(
+            config
+                .triggers
+                .iter()
+                .filter(|a| a.source == source)
+                .cloned(),
+        );
+        d.errors.extend(
+            check(source_config, &triggers)
+                .into_iter()
+                .map(|e| format!("source `{source}`: {e}")),
+        );
+    }
+}
+
+fn check_events(config: &Config, d: &mut Diagnostics) {
+    // Events triggering each event through an action.
+    let mut graph: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for action in config.actions.iter() {
+        graph
+            .entry(action.event.as_str())
+            .or_default()
+            .insert(action.emit.as_str());
+    }
+
+    let consumed = BTreeSet::from_iter(config.actions.iter().map(|a| a.event.as_str()).chain(
+        config.triggers.iter().filter_map(|a| {
+            // Sent back to the webhook caller.
+            a.arguments.get("response-event")?.as_str()
+        }),
+    ));
+    let emitted = BTreeSet::from_iter(
+        config
+            .triggers
+            .iter()
+            .map(|a| a.emit.as_str())
+            .chain(config.actions.iter().map(|a| a.emit.as_str())),
+    );
+    for event in emitted.difference(&consumed) {
+        d.warnings
+            .push(format!("event `{event}` is emitted but never consumed"));
+    }
+
+    let triggered = reachable(&graph, config.triggers.iter().map(|a| a.emit.as_str()));
+    for action in config.actions.iter() {
+        if !triggered.contains(action.event.as_str()) {
+            d.warnings.push(format!(
+                "action `{}` is unreachable: no trigger leads to event `{}`",
+                action.name(),
+                action.event
+            ));
+        }
+    }
+
+    // Events reachable through at least one action.
+    let successors = |event: &str| reachable(&graph, graph[event].iter().copied());
+    let mut in_cycle = BTreeSet::new();
+    for &event in graph.keys() {
+        if in_cycle.contains(event) {
+            continue;
+        }
+        let from = successors(event);
+        if !from.contains(event) {
+            continue;
+        }
+        // Every event of the cycle has actions, so it is in the graph.
+        let cycle = Vec::from_iter(
+            from.iter()
+                .copied()
+                .filter(|a| graph.contains_key(a) && successors(a).contains(event)),
+        );
+        d.warnings.push(format!(
+            "events form a cycle: {}",
+            cycle
+                .iter()
+                .map(|a| format!("`{a}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        in_cycle.extend(cycle);
+    }
+}
+
+/// Events reachable from the given ones, including themselves.
+fn reachable<'a>(
+    graph: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    start: impl Iterator<Item = &'a str>,
+) -> BTreeSet<&'a str> {
+    let mut visited = BTreeSet::new();
+    let mut stack = Vec::from_iter(start);
+    while let Some(event) = stack.pop() {
+        if !visited.insert(event) {
+            continue;
+        }
+        if let Some(next) = graph.get(event) {
+            stack.extend(next.iter().copied());
+        }
+    }
+    visited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::triggers::{cron::CronTrigger, webhook::WebhookTrigger, Trigger, TriggerSource};
+
+    fn no_check(_: &toml::Table, _: &[Trigger]) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn validate(s: &str) -> Diagnostics {
+        let config = Config::new(s).unwrap();
+        super::validate(
+            &config,
+            |a| a == "record",
+            |a| (a == "once").then_some(no_check as SourceCheck),
+        )
+    }
+
+    #[test]
+    fn cycle() {
+        let diagnostics = validate(
+            r#"
+            [sources.once]
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "start"
+            emit = "recorded"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "recorded"
+            emit = "start"
+            accepted-input = "Err"
+            arguments = []
+            "#,
+        );
+        assert!(diagnostics.is_ok());
+        assert_eq!(
+            diagnostics.warnings,
+            vec!["events form a cycle: `recorded`, `start`"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let diagnostics = validate(
+            r#"
+            [sources.cron]
+            [handlers.untyped]
+            [handlers.sql]
+            type = "sql"
+
+            [[triggers]]
+            source = "cron"
+            emit = "tick"
+            arguments = "* * * * * *"
+
+            [[triggers]]
+            source = "once"
+            emit = "tick"
+            arguments = []
+
+            [[actions]]
+            name = "store"
+            handler = "database"
+            event = "tick"
+            emit = "stored"
+            arguments = []
+            "#,
+        );
+        assert_eq!(
+            diagnostics.errors,
+            vec![
+                "handler `sql`: unknown handler type `sql`",
+                "handler `untyped`: missing key `type`",
+                "action `store`: handler `database` is not defined",
+                "unknown source type `cron`",
+                "source `once` has triggers but no config",
+            ]
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let config = Config::new(
+            r#"
+            [sources.cron]
+            [sources.webhook]
+            [handlers.record]
+            type = "record"
+            max-concurrent = 0
+
+            [[triggers]]
+            source = "cron"
+            emit = "tick"
+            arguments = "0 0 25 * * *"
+
+            [[triggers]]
+            source = "cron"
+            emit = "tock"
+            arguments = { schedule = "0 0 8 * * *", catch-up = "fire-all" }
+
+            [[triggers]]
+            source = "webhook"
+            emit = "tick"
+            arguments = { path = "hook" }
+
+            [[actions]]
+            handler = "record"
+            event = "tick"
+            emit = "tock"
+            arguments = []
+            "#,
+        )
+        .unwrap();
+        let diagnostics = super::validate(
+            &config,
+            |a| a == "record",
+            |a| match a {
+                "cron" => Some(CronTrigger::check as SourceCheck),
+                "webhook" => Some(WebhookTrigger::check),
+                _ => None,
+            },
+        );
+        assert_eq!(diagnostics.errors.len(), 4);
+        assert_eq!(
+            diagnostics.errors[0],
+            "handler `record`: invalid limits: invalid value: integer `0`, \
+            expected a nonzero usize in `max-concurrent`"
+        );
+        // Every trigger is checked, not only the first invalid one.
+        assert!(diagnostics.errors[1].starts_with(
+            "source `cron`: invalid value: Cron arguments for trigger `tick`: \
+            invalid cron expression `0 0 25 * * *`"
+        ));
+        assert_eq!(
+            diagnostics.errors[2..],
+            [
+                "source `cron`: missing value: Cron arguments for trigger `tock`: \
+                catch-up other than `skip` requires `state-path` in the source config",
+                "source `webhook`: invalid value: Webhook arguments: path has to start with `/`: hook",
+            ]
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        let diagnostics = validate(
+            r#"
+            [sources.once]
+            [handlers.record]
+            type = "record"
+
+            [[triggers]]
+            source = "once"
+            emit = "start"
+            arguments = { response-event = "done" }
+
+            [[actions]]
+            handler = "record"
+            event = "start"
+            emit = "done"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "start"
+            emit = "logged"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "orphan"
+            emit = "orphan-done"
+            arguments = []
+
+            [[actions]]
+            handler = "record"
+            event = "orphan-done"
+            emit = "orphan"
+            arguments = []
+            "#,
+        );
+        assert!(diagnostics.is_ok());
+        assert_eq!(
+            diagnostics.warnings,
+            vec![
+                "event `logged` is emitted but never consumed",
+                "action `orphan -> orphan-done` is unreachable: no trigger leads to event `orphan`",
+                "action `orphan-done -> orphan` is unreachable: no trigger leads to event `orphan-done`",
+                "events form a cycle: `orphan`, `orphan-done`",
+            ]
+        );
+    }
+}
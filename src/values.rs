@@ -1,3 +1,4 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
 use serde::{de, Deserialize};
 use std::collections::HashMap;
 
@@ -9,6 +10,8 @@ pub enum Value {
     Integer(i64),
     Float(f64),
     String(String),
+    Bytes(Vec<u8>),
+    DateTime(DateTime<Utc>),
     Array(Vec<Value>),
     Map(HashMap<String, Value>),
 }
@@ -40,6 +43,18 @@ impl Value {
         }
         None
     }
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        if let Value::Bytes(b) = self {
+            return Some(b);
+        }
+        None
+    }
+    pub fn as_datetime(&self) -> Option<&DateTime<Utc>> {
+        if let Value::DateTime(d) = self {
+            return Some(d);
+        }
+        None
+    }
     pub fn as_array(&self) -> Option<&Vec<Self>> {
         if let Value::Array(a) = self {
             return Some(a);
@@ -67,6 +82,8 @@ impl Value {
             Self::Integer(i) => pad(&format!("{i}")),
             Self::Float(f) => pad(&format!("{f}")),
             Self::String(s) => pad(&format!("\"{s}\"")),
+            Self::Bytes(b) => pad(&format!("<{} bytes>", b.len())),
+            Self::DateTime(d) => pad(&d.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
             Self::Array(a) => {
                 if a.is_empty() {
                     pad("[]")
@@ -102,6 +119,63 @@ impl Value {
     }
 }
 
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Self::Integer(i),
+                None => n.as_f64().map(Self::Float).unwrap_or_default(),
+            },
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Array(a) => Self::Array(a.into_iter().map(Self::from).collect()),
+            serde_json::Value::Object(o) => {
+                Self::Map(o.into_iter().map(|(k, v)| (k, Self::from(v))).collect())
+            }
+        }
+    }
+}
+
+/// JSON has no bytes or datetime types, bytes become arrays of integers
+/// and datetimes RFC 3339 strings.
+impl From<&Value> for serde_json::Value {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(b) => Self::Bool(*b),
+            Value::Integer(i) => Self::from(*i),
+            Value::Float(f) => serde_json::Number::from_f64(*f)
+                .map(Self::Number)
+                .unwrap_or_default(),
+            Value::String(s) => Self::String(s.clone()),
+            Value::Bytes(b) => Self::Array(b.iter().map(|a| Self::from(*a)).collect()),
+            Value::DateTime(d) => Self::String(d.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            Value::Array(a) => Self::Array(a.iter().map(Self::from).collect()),
+            Value::Map(m) => {
+                Self::Object(m.iter().map(|(k, v)| (k.clone(), Self::from(v))).collect())
+            }
+        }
+    }
+}
+
+/// Parse an ISO 8601 timestamp. Values without an offset are assumed to be UTC.
+pub(crate) fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d.to_utc());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(d.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
 struct ValueVisitor;
 impl<'de> de::Visitor<'de> for ValueVisitor {
     type Value = Value;
@@ -145,6 +219,10 @@ impl<'de> de::Visitor<'de> for ValueVisitor {
         Ok(Value::String(v.to_string()))
     }
 
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
     fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
     where
         V: de::SeqAccess<'de>,
@@ -234,6 +312,59 @@ mod tests {
         );
     }
     #[test]
+    fn from_json() {
+        let json = serde_json::json!({ "a": [1, 2.5, null], "b": { "c": "hello", "d": true } });
+        let value = Value::from(json);
+        let map = value.as_map().unwrap();
+        assert_eq!(
+            map["a"],
+            Value::Array(vec![Value::Integer(1), Value::Float(2.5), Value::Null])
+        );
+        assert_eq!(
+            map["b"],
+            Value::Map(HashMap::from_iter(vec![
+                ("c".to_string(), Value::String("hello".to_string())),
+                ("d".to_string(), Value::Bool(true)),
+            ]))
+        );
+    }
+    #[test]
+    fn to_json() {
+        let value = Value::Map(HashMap::from_iter(vec![
+            (
+                "a".to_string(),
+                Value::Array(vec![Value::Integer(1), Value::Float(2.5)]),
+            ),
+            ("b".to_string(), Value::Bytes(vec![1, 2])),
+            (
+                "c".to_string(),
+                Value::DateTime(DateTime::from_timestamp(1771072386, 0).unwrap()),
+            ),
+            ("d".to_string(), Value::Float(f64::NAN)),
+        ]));
+        assert_eq!(
+            serde_json::Value::from(&value),
+            serde_json::json!({
+                "a": [1, 2.5],
+                "b": [1, 2],
+                "c": "2026-02-14T12:33:06Z",
+                "d": null,
+            })
+        );
+    }
+    #[test]
+    fn parse_datetimes() {
+        let expected = DateTime::from_timestamp(1771072386, 0).unwrap();
+        assert_eq!(parse_datetime("2026-02-14T12:33:06Z"), Some(expected));
+        assert_eq!(parse_datetime("2026-02-14T13:33:06+01:00"), Some(expected));
+        assert_eq!(parse_datetime("2026-02-14 12:33:06"), Some(expected));
+        assert_eq!(
+            parse_datetime("2026-02-14"),
+            DateTime::from_timestamp(1771027200, 0)
+        );
+        assert_eq!(parse_datetime("yesterday"), None);
+    }
+    #[test]
     fn deserialize_nested() {
         let toml_value = r#"key = { a = [1, 2, 3], b = { c = "hello" } }"#;
         let value = toml::from_str::<Value>(toml_value).unwrap();